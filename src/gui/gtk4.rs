@@ -1,11 +1,13 @@
 use adw::prelude::*;
 use adw::{ApplicationWindow, HeaderBar};
-use adw::gtk::{Application, Orientation, Entry, DrawingArea};
+use adw::gtk::{glib, Application, Orientation, Entry, DrawingArea, EventControllerMotion, GestureClick};
 
 use std::rc::Rc;
 use std::cell::RefCell;
 
-use crate::renderer::Doc;
+use mehweb::renderer::Doc;
+use mehweb::renderer::web::css::media::Device;
+use mehweb::protocols::{DocLoader, Resource};
 use crate::gui::Gui;
 
 pub struct Gtk4Gui {
@@ -28,15 +30,20 @@ impl Gui for Gtk4Gui {
 	app.connect_activate(move |app| {
 	    // holds header bar and browser engine
 	    let content = adw::gtk::Box::new(Orientation::Vertical, 0);
+	    // shows the title of the page
+	    let title = adw::WindowTitle::new("MehWeb", "");
             content.append(
 		&HeaderBar::builder()
-                    .title_widget(&adw::WindowTitle::new("MehWeb", ""))
+                    .title_widget(&title)
                     .build(),
             );
 
 	    // copy of document to be used in app
 	    let document = Rc::clone(&document);
 	    let document_setter = Rc::clone(&document);
+	    let document_pointer = Rc::clone(&document);
+	    // urls of pages that have been opened, for :visited
+	    let history: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
 
 	    let drawing_area = DrawingArea::new();
 	    // draws document
@@ -55,15 +62,131 @@ impl Gui for Gtk4Gui {
 	    drawing_area.set_size_request(500, 500);
 	    content.append(&drawing_area);
 
+	    // the pointer changes :hover
+	    let motion = EventControllerMotion::new();
+	    {
+		let document = Rc::clone(&document_pointer);
+		let drawing_area = drawing_area.clone();
+		motion.connect_motion(move |_, x, y| {
+		    let doc = Rc::clone(&document.borrow());
+		    if doc.pointer_moved(Some((x, y))) {
+			doc.render();
+			drawing_area.queue_draw();
+		    }
+		});
+	    }
+	    {
+		let document = Rc::clone(&document_pointer);
+		let drawing_area = drawing_area.clone();
+		motion.connect_leave(move |_| {
+		    let doc = Rc::clone(&document.borrow());
+		    if doc.pointer_moved(None) {
+			doc.render();
+			drawing_area.queue_draw();
+		    }
+		});
+	    }
+	    drawing_area.add_controller(&motion);
+	    // clicking changes :active and :focus
+	    let click = GestureClick::new();
+	    {
+		let document = Rc::clone(&document_pointer);
+		let drawing_area = drawing_area.clone();
+		click.connect_pressed(move |_, _, x, y| {
+		    let doc = Rc::clone(&document.borrow());
+		    if doc.pointer_pressed(x, y) {
+			doc.render();
+			drawing_area.queue_draw();
+		    }
+		});
+	    }
+	    {
+		let document = Rc::clone(&document_pointer);
+		let drawing_area = drawing_area.clone();
+		click.connect_released(move |_, _, _, _| {
+		    let doc = Rc::clone(&document.borrow());
+		    if doc.pointer_released() {
+			doc.render();
+			drawing_area.queue_draw();
+		    }
+		});
+	    }
+	    drawing_area.add_controller(&click);
+	    // media queries can change when the page is resized
+	    {
+		let document = Rc::clone(&document_pointer);
+		drawing_area.connect_resize(move |drawing_area, _, _| {
+		    let doc = Rc::clone(&document.borrow());
+		    if doc.set_device(device(drawing_area)) {
+			doc.render();
+			drawing_area.queue_draw();
+		    }
+		});
+	    }
+
 	    // the urlbar
 	    let urlbar = Entry::new();
 	    urlbar.connect_activate(move |entry| {
-		let url = entry.buffer().text();
+		// links are resolved to full urls, so the history needs the full url too for :visited to match them
+		let url = mehweb::protocols::normalize_url(&entry.buffer().text());
+		// the page, and the stylesheets and fonts it needs, are read on other threads and sent back here
+		let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+		let mut loader = DocLoader::new(&url);
+		history.borrow_mut().push(url.clone());
+		loader.set_history(&history.borrow());
+		loader.doc().set_device(device(&drawing_area));
+		title.set_subtitle(&url);
+		{
+		    let sender = sender.clone();
+		    std::thread::spawn(move || {
+			// a panic while loading still has to let the gui know, or the page would never be finished
+			let loaded = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			    mehweb::protocols::fetch(&url, &mut |chunk| {
+				let _ = sender.send(Loaded::Chunk(chunk.to_vec()));
+			    })
+			}));
+			if let Ok(Err(error)) = loaded {
+			    eprintln!("Couldn't load {}: {}", url, error);
+			}
+			// let the gui know the page is done
+			let _ = sender.send(Loaded::Done);
+		    });
+		}
+		// show the document while it loads
 		let document = Rc::clone(&document_setter);
-		let doc = crate::protocols::load_doc(url);
-		doc.render();
-		*document.borrow_mut() = doc;
-		drawing_area.queue_draw();
+		*document.borrow_mut() = loader.doc();
+		let drawing_area = drawing_area.clone();
+		let title = title.clone();
+		let mut done = false;
+		receiver.attach(None, move |loaded: Loaded| {
+		    // a page that was left doesn't need to finish loading
+		    if !Rc::ptr_eq(&document.borrow(), &loader.doc()) {
+			return glib::Continue(false);
+		    }
+		    match loaded {
+			Loaded::Chunk(bytes) => loader.feed(&bytes),
+			Loaded::Done => {
+			    loader.finish();
+			    done = true;
+			},
+			Loaded::Resource(resource, bytes) => loader.loaded(resource, bytes),
+		    }
+		    for resource in loader.take_requests() {
+			let sender = sender.clone();
+			std::thread::spawn(move || {
+			    let bytes = std::panic::catch_unwind(|| mehweb::protocols::fetch_resource(&resource)).unwrap_or(None);
+			    let _ = sender.send(Loaded::Resource(resource, bytes));
+			});
+		    }
+		    // pages without a title just show the name of the browser
+		    match loader.metadata().title.filter(|title| !title.is_empty()) {
+			Some(page_title) => title.set_title(&page_title),
+			None => title.set_title("MehWeb"),
+		    }
+		    loader.doc().render();
+		    drawing_area.queue_draw();
+		    glib::Continue(!done || loader.is_loading())
+		});
 	    });
 	    content.append(&urlbar);
 	    
@@ -81,3 +204,22 @@ impl Gui for Gtk4Gui {
 	self.app.run();
     }
 }
+
+// what the threads loading a page send back to the gui
+enum Loaded {
+    // the next piece of the page's html
+    Chunk(Vec<u8>),
+    // all of the html has arrived
+    Done,
+    // a stylesheet or font the page asked for (None if it couldn't be loaded)
+    Resource(Resource, Option<Vec<u8>>),
+}
+
+// the screen that media queries are checked against
+fn device(drawing_area: &DrawingArea) -> Device {
+    Device{width: drawing_area.width() as f64,
+	   height: drawing_area.height() as f64,
+	   resolution: drawing_area.scale_factor() as f64,
+	   dark: adw::StyleManager::default().is_dark(),
+	   print: false}
+}
@@ -0,0 +1,3 @@
+pub mod renderer;
+pub mod rules;
+pub mod protocols;
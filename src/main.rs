@@ -1,7 +1,4 @@
-mod renderer;
 mod gui;
-mod rules;
-mod protocols;
 
 use crate::gui::{gtk4, Gui};
 
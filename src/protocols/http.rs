@@ -1,24 +1,77 @@
 use std::net::TcpStream;
-use std::io::{Read, Write};
+use std::io::{Read, Write, Error, ErrorKind};
 
-pub fn load(url: &str) -> String {
+// gets the body of a page, giving it to on_chunk a piece at a time as it arrives
+pub fn stream(url: &str, on_chunk: &mut dyn FnMut(&[u8])) -> std::io::Result<()> {
     // separate url into host and path
-    let (host, path) = url.split_once("/").unwrap();
+    let (host, path) = url.split_once("/").unwrap_or((url, ""));
     
     // connect the tcpstream
-    let mut stream = TcpStream::connect(format!("{}:80", host)).unwrap();
+    let mut stream = TcpStream::connect(format!("{}:80", host))?;
     
     // GET request
-    stream.write(format!("GET /{} HTTP/1.0\r\n", path).as_bytes()).unwrap();
+    stream.write_all(format!("GET /{} HTTP/1.0\r\n", path).as_bytes())?;
 
     // Host header (required)
-    stream.write(format!("Host: {}\r\n", host).as_bytes()).unwrap();
+    stream.write_all(format!("Host: {}\r\n", host).as_bytes())?;
 
     // end request
-    stream.write(b"\r\n").unwrap();
+    stream.write_all(b"\r\n")?;
 
-    // return full http response
-    let mut response = String::new();
-    let _ = stream.read_to_string(&mut response);
-    return response;
+    // headers come before the body, so hold on to everything until they end
+    let mut headers = Vec::new();
+    let mut status = None;
+    let mut in_body = false;
+    let mut buffer = [0; 4096];
+    loop {
+	let read = stream.read(&mut buffer)?;
+	if read == 0 {
+	    break;
+	}
+	if in_body {
+	    on_chunk(&buffer[..read]);
+	} else {
+	    headers.extend_from_slice(&buffer[..read]);
+	    // separate headers from body
+	    if let Some(end) = headers.windows(4).position(|w| w == b"\r\n\r\n") {
+		status = Some(parse_status(&String::from_utf8_lossy(&headers[..end]))?);
+		in_body = true;
+		on_chunk(&headers[end+4..]);
+	    }
+	}
+    }
+    // error pages still have a body to show, but whoever asked for it should know it isn't what they wanted
+    match status {
+	Some((code, _)) if (200..300).contains(&code) => Ok(()),
+	Some((code, reason)) => Err(Error::other(format!("HTTP {} {}", code, reason))),
+	None => Err(Error::new(ErrorKind::UnexpectedEof, "connection closed before the headers ended")),
+    }
+}
+
+// gets the status code and reason from the first line of the headers
+fn parse_status(headers: &str) -> std::io::Result<(u16, String)> {
+    let line = headers.lines().next().unwrap_or("");
+    let mut parts = line.splitn(3, ' ');
+    let code = match (parts.next(), parts.next()) {
+	(Some(version), Some(code)) if version.starts_with("HTTP/") => code.parse().ok(),
+	_ => None,
+    };
+    match code {
+	Some(code) => Ok((code, parts.next().unwrap_or("").to_string())),
+	None => Err(Error::new(ErrorKind::InvalidData, format!("invalid status line: {}", line))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_lines() {
+	assert_eq!(parse_status("HTTP/1.0 200 OK\r\nContent-Type: text/html").unwrap(), (200, "OK".to_string()));
+	assert_eq!(parse_status("HTTP/1.1 404 Not Found").unwrap(), (404, "Not Found".to_string()));
+	assert_eq!(parse_status("HTTP/1.1 204").unwrap(), (204, String::new()));
+	assert!(parse_status("<html>").is_err());
+	assert!(parse_status("HTTP/1.1 abc OK").is_err());
+    }
 }
@@ -1,26 +1,584 @@
 mod http;
 mod file;
 
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::cell::{Cell, RefCell};
 
+use crate::rules;
 use crate::renderer::{Doc, web::{css, html}};
+use crate::renderer::web::css::{StyleRule, Stylesheet};
+use crate::renderer::web::css::cascade::Origin;
+use crate::renderer::web::css::media::{self, Device};
+use crate::renderer::web::font::Fonts;
 
-pub fn load_doc(url: String) -> Rc<Doc> {
+// reads what's at a url, giving it to on_chunk a piece at a time as it arrives
+pub fn fetch(url: &str, on_chunk: &mut dyn FnMut(&[u8])) -> std::io::Result<()> {
     if url.starts_with("http://") {
-	let http_return = http::load(url.trim_start_matches("http://"));
-	println!("{}", http_return);
-
-	// separate headers from body
-	let (_headers, body) = http_return.split_once("\r\n\r\n").unwrap();
-	
-	// parse body to Doc
-	let parsed_html = html::parse(body.to_string());
-	let css = parsed_html.find_css();
-	let parsed_css = css::parse(css);
-	html::apply_css(parsed_css.clone(), Rc::clone(&parsed_html));
-	println!("{}", parsed_html);
-	return Rc::new(Doc::Web(parsed_html));
+	http::stream(url.trim_start_matches("http://"), on_chunk)
+    } else {
+	file::stream(url.trim_start_matches("file://"), on_chunk)
+    }
+}
+
+// something a page needs besides its html
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resource {
+    Stylesheet(String),
+    Font(String),
+}
+
+// reads all of a resource (None if it couldn't be read)
+pub fn fetch_resource(resource: &Resource) -> Option<Vec<u8>> {
+    let (kind, url) = match resource {
+	Resource::Stylesheet(url) => ("stylesheet", url),
+	Resource::Font(url) => ("font", url),
+    };
+    let mut bytes = Vec::new();
+    match fetch(url, &mut |chunk| bytes.extend_from_slice(chunk)) {
+	Ok(()) => Some(bytes),
+	Err(error) => {
+	    eprintln!("Couldn't load {} {}: {}", kind, url, error);
+	    None
+	},
+    }
+}
+
+// loads a page and everything it needs, waiting for all of it
+pub fn load_doc(url: String) -> Rc<Doc> {
+    let mut loader = DocLoader::new(&url);
+    if let Err(error) = fetch(&url, &mut |chunk| loader.feed(chunk)) {
+	eprintln!("Couldn't load {}: {}", url, error);
+    }
+    loader.finish();
+    loop {
+	let requests = loader.take_requests();
+	if requests.is_empty() {
+	    break;
+	}
+	for resource in requests {
+	    let bytes = fetch_resource(&resource);
+	    loader.loaded(resource, bytes);
+	}
+    }
+    loader.doc()
+}
+
+// turns a url that might be relative into a full one, based on the url of the page it's on
+pub fn resolve_url(base: &str, url: &str) -> String {
+    let url = url.trim();
+    // urls with a scheme are already full
+    if let Some((scheme, _)) = url.split_once(':') {
+	if scheme.len() > 1 && scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.') {
+	    return url.to_string();
+	}
+    }
+    // plain paths are files
+    let (scheme, rest) = base.split_once("://").unwrap_or(("file", base));
+    if let Some(url) = url.strip_prefix("//") {
+	return format!("{}://{}", scheme, url);
+    }
+    // file urls don't have a host
+    let (host, path) = if scheme == "file" {
+	("", rest.to_string())
     } else {
-	Rc::new(file::load(url.trim_start_matches("file://").to_string()))
+	match rest.find('/') {
+	    Some(index) => (&rest[..index], rest[index..].to_string()),
+	    None => (rest, "/".to_string()),
+	}
+    };
+    let path_without_fragment = path.split('#').next().unwrap_or("");
+    let path_without_query = path_without_fragment.split('?').next().unwrap_or("");
+    let new_path = if url.is_empty() {
+	path_without_fragment.to_string()
+    } else if url.starts_with('#') {
+	path_without_fragment.to_string() + url
+    } else if url.starts_with('?') {
+	path_without_query.to_string() + url
+    } else if url.starts_with('/') {
+	url.to_string()
+    } else {
+	// relative to the directory the page is in
+	let directory = match path_without_query.rfind('/') {
+	    Some(index) => &path_without_query[..index+1],
+	    None => "",
+	};
+	directory.to_string() + url
+    };
+    format!("{}://{}{}", scheme, host, remove_dot_segments(&new_path))
+}
+
+// the full form of a url as it was typed, which is what links to it resolve to (so plain paths become file urls)
+pub fn normalize_url(url: &str) -> String {
+    resolve_url(url, url)
+}
+
+// gets rid of "." and ".." in a path
+fn remove_dot_segments(path: &str) -> String {
+    // the query and fragment are left alone
+    let split = path.find(['?', '#']).unwrap_or(path.len());
+    let (path, rest) = path.split_at(split);
+    let mut segments: Vec<&str> = Vec::new();
+    let parts = path.split('/').collect::<Vec<&str>>();
+    for (index, part) in parts.iter().enumerate() {
+	let last = index == parts.len()-1;
+	match *part {
+	    "." => if last { segments.push("") },
+	    ".." => {
+		// can't go above the root
+		if segments.len() > 1 || (segments.len() == 1 && !segments[0].is_empty()) {
+		    segments.pop();
+		}
+		if last {
+		    segments.push("");
+		}
+	    },
+	    _ => segments.push(part),
+	}
+    }
+    segments.join("/") + rest
+}
+
+// where the user's stylesheet is, which is $XDG_CONFIG_HOME/mehweb/user.css (or ~/.config/mehweb/user.css)
+fn user_stylesheet_path() -> Option<std::path::PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME").filter(|config| !config.is_empty()) {
+	Some(config) => std::path::PathBuf::from(config),
+	None => std::path::PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("mehweb").join("user.css"))
+}
+
+// builds a document from bytes as they arrive, so it can be shown before it's done loading
+// (stylesheets and fonts aren't loaded by the loader, which asks for them with take_requests and gets them with loaded)
+pub struct DocLoader {
+    parser: html::Parser,
+    // document being built
+    doc: Rc<Doc>,
+    // url the document came from
+    url: String,
+    // bytes at the end of the last chunk that aren't a whole character yet
+    partial: Vec<u8>,
+    // the browser's stylesheet, which goes before the user's and the document's
+    user_agent: Stylesheet,
+    // where the user's stylesheet is, if they have one
+    user_stylesheet: Option<String>,
+    // where the css that is currently applied came from (None before any is applied)
+    css: Option<Vec<html::StyleSource>>,
+    // linked and imported stylesheets that have already been loaded, by url
+    stylesheets: HashMap<String, String>,
+    // font files that have already been loaded, by url (None if they couldn't be)
+    fonts: HashMap<String, Option<Rc<Vec<u8>>>>,
+    // resources that haven't been asked for yet, and ones that have been asked for but haven't arrived
+    requests: Vec<Resource>,
+    pending: Vec<Resource>,
+    // urls of pages that have been visited, for :visited
+    history: Vec<String>,
+    // the title, base url, etc. of the document so far, as they are written in it
+    metadata: html::DocumentMetadata,
+}
+impl DocLoader {
+    pub fn new(url: &str) -> DocLoader {
+	let parser = html::Parser::new();
+	let doc = Rc::new(Doc::Web(parser.document(), RefCell::new(Vec::new()), Cell::new(Device::default()), Rc::new(RefCell::new(Fonts::default()))));
+	DocLoader{parser,
+		  doc,
+		  url: url.to_string(),
+		  partial: Vec::new(),
+		  user_agent: css::parse(rules::DEFAULT_CSS.to_string(), Origin::UserAgent),
+		  user_stylesheet: user_stylesheet_path().filter(|path| path.is_file()).map(|path| format!("file://{}", path.display())),
+		  css: None,
+		  stylesheets: HashMap::new(),
+		  fonts: HashMap::new(),
+		  requests: Vec::new(),
+		  pending: Vec::new(),
+		  history: Vec::new(),
+		  metadata: html::DocumentMetadata::default()}
+    }
+    // sets which pages have been visited, so links to them can be styled differently
+    pub fn set_history(&mut self, history: &[String]) {
+	self.history = history.to_vec();
+	self.update_visited(&self.parser.document().descendant_elements());
+    }
+    // the document so far
+    pub fn doc(&self) -> Rc<Doc> {
+	Rc::clone(&self.doc)
+    }
+    // parses the next chunk of bytes
+    pub fn feed(&mut self, bytes: &[u8]) {
+	self.partial.extend_from_slice(bytes);
+	let mut text = String::new();
+	loop {
+	    match std::str::from_utf8(&self.partial) {
+		Ok(valid) => {
+		    text += valid;
+		    self.partial.clear();
+		    break;
+		},
+		Err(error) => {
+		    let valid_up_to = error.valid_up_to();
+		    text += std::str::from_utf8(&self.partial[..valid_up_to]).unwrap();
+		    match error.error_len() {
+			// invalid bytes become replacement characters
+			Some(len) => {
+			    text.push('\u{fffd}');
+			    self.partial.drain(..valid_up_to+len);
+			},
+			// the rest of the character is in the next chunk
+			None => {
+			    self.partial.drain(..valid_up_to);
+			    break;
+			},
+		    }
+		},
+	    }
+	}
+	self.parser.feed(&text);
+	self.update();
+    }
+    // parses whatever is left once all of the html has arrived
+    pub fn finish(&mut self) {
+	if !self.partial.is_empty() {
+	    let rest = String::from_utf8_lossy(&self.partial).to_string();
+	    self.partial.clear();
+	    self.parser.feed(&rest);
+	}
+	self.parser.finish();
+	self.update();
+    }
+    // the stylesheets and fonts the document needs that haven't been asked for yet
+    pub fn take_requests(&mut self) -> Vec<Resource> {
+	std::mem::take(&mut self.requests)
+    }
+    // whether resources that have been asked for are still loading
+    pub fn is_loading(&self) -> bool {
+	!self.pending.is_empty()
+    }
+    // gives the loader a resource it asked for (None if it couldn't be loaded), and restyles the document with it
+    pub fn loaded(&mut self, resource: Resource, bytes: Option<Vec<u8>>) {
+	self.pending.retain(|pending| pending != &resource);
+	match resource {
+	    // a stylesheet that can't be loaded just doesn't apply
+	    Resource::Stylesheet(url) => {
+		self.stylesheets.insert(url, bytes.map(|bytes| String::from_utf8_lossy(&bytes).to_string()).unwrap_or_default());
+	    },
+	    Resource::Font(url) => {
+		self.fonts.insert(url, bytes.map(Rc::new));
+	    },
+	}
+	// the css is put together again now that it has more of what it needs
+	self.css = None;
+	self.update();
+    }
+    // catches up with what the parser changed, only looking at the elements that are new
+    fn update(&mut self) {
+	let changed = self.parser.take_changes();
+	let has = |tag_names: &[&str]| changed.iter().any(|element| tag_names.contains(&element.tag_name().unwrap_or_default()));
+	if has(&["title", "base", "link", "meta"]) {
+	    self.metadata = self.parser.document().metadata();
+	}
+	// links are relative to <base>, so it can change all of them
+	if has(&["base"]) {
+	    self.update_visited(&self.parser.document().descendant_elements());
+	} else {
+	    self.update_visited(&changed);
+	}
+	if self.css.is_none() || has(&["style", "link", "base"]) {
+	    self.update_css();
+	}
+	self.doc.restyle();
+    }
+    // loads the css of the document again, restyling everything if it changed
+    fn update_css(&mut self) {
+	let document = self.parser.document();
+	let sources = document.find_css();
+	if self.css.as_ref() != Some(&sources) {
+	    // stylesheets are parsed separately, but the rules keep their document order
+	    let mut css_rules = self.user_agent.rules.clone();
+	    let mut font_faces = self.user_agent.font_faces.clone();
+	    if let Some(url) = self.user_stylesheet.clone() {
+		let css = self.load_stylesheet(&url);
+		let mut user = self.load_imports(css::parse(css, Origin::User), &url, Origin::User, &[]);
+		css_rules.append(&mut user.rules);
+		font_faces.append(&mut user.font_faces);
+	    }
+	    let base_url = self.base_url();
+	    for source in sources.iter() {
+		let (stylesheet, media) = match source {
+		    html::StyleSource::Inline(css, media) => (self.load_imports(css::parse(css.to_string(), Origin::Author), &base_url, Origin::Author, &[]), media),
+		    html::StyleSource::Link(href, media) => {
+			let url = resolve_url(&base_url, href);
+			let css = self.load_stylesheet(&url);
+			(self.load_imports(css::parse(css, Origin::Author), &url, Origin::Author, &[]), media)
+		    },
+		};
+		let (mut rules, mut faces) = (stylesheet.rules, stylesheet.font_faces);
+		// the media attribute applies to every rule in the stylesheet
+		let media = media::parse(media);
+		for rule in rules.iter_mut() {
+		    rule.media.push(media.clone());
+		}
+		css_rules.append(&mut rules);
+		font_faces.append(&mut faces);
+	    }
+	    let doc = Rc::clone(&self.doc);
+	    if let Doc::Web(_, doc_css_rules, _, fonts) = &*doc {
+		*doc_css_rules.borrow_mut() = css_rules;
+		// the first source of each font that can be loaded is used
+		let mut fonts = fonts.borrow_mut();
+		fonts.clear();
+		for font_face in font_faces {
+		    for url in font_face.sources.iter() {
+			match self.fonts.get(url) {
+			    Some(Some(data)) => if fonts.add(&font_face, Rc::clone(data)) {
+				break;
+			    },
+			    Some(None) => {},
+			    // later sources aren't tried until this one has arrived
+			    None => {
+				self.request(Resource::Font(url.to_string()));
+				break;
+			    },
+			}
+		    }
+		}
+	    }
+	    self.css = Some(sources);
+	    document.invalidate_style();
+	}
+    }
+    // marks links to pages in the history as visited
+    fn update_visited(&self, elements: &[Rc<html::Node>]) {
+	let base_url = self.base_url();
+	for element in elements {
+	    if element.is_link() {
+		let url = resolve_url(&base_url, &element.get_attribute("href").unwrap_or_default());
+		element.set_visited(self.history.contains(&url));
+	    }
+	}
+    }
+    // the metadata of the document so far, with its urls made full
+    pub fn metadata(&self) -> html::DocumentMetadata {
+	let mut metadata = self.metadata.clone();
+	let base_url = self.base_url();
+	metadata.favicon = metadata.favicon.map(|favicon| resolve_url(&base_url, &favicon));
+	metadata.canonical = metadata.canonical.map(|canonical| resolve_url(&base_url, &canonical));
+	metadata.base_url = Some(base_url);
+	metadata
+    }
+    // url that relative urls in the document are relative to
+    pub fn base_url(&self) -> String {
+	match &self.metadata.base_url {
+	    Some(base_url) => resolve_url(&self.url, base_url),
+	    None => self.url.clone(),
+	}
+    }
+    // adds the rules of the stylesheets a stylesheet imports in front of its own, and makes its font urls full
+    // (importing is the urls of the stylesheets that imported this one, so that imports can't go in circles)
+    fn load_imports(&mut self, stylesheet: Stylesheet, url: &str, origin: Origin, importing: &[String]) -> Stylesheet {
+	let mut importing = importing.to_vec();
+	importing.push(url.to_string());
+	let mut rules: Vec<StyleRule> = Vec::new();
+	let mut font_faces = Vec::new();
+	for import in stylesheet.imports.iter() {
+	    let import_url = resolve_url(url, &import.url);
+	    if importing.contains(&import_url) {
+		continue;
+	    }
+	    let css = self.load_stylesheet(&import_url);
+	    let mut imported = self.load_imports(css::parse(css, origin), &import_url, origin, &importing);
+	    // rules that are imported only apply when the import's media queries match
+	    for rule in imported.rules.iter_mut() {
+		rule.media.insert(0, import.media.clone());
+	    }
+	    rules.append(&mut imported.rules);
+	    font_faces.append(&mut imported.font_faces);
+	}
+	rules.extend(stylesheet.rules);
+	for mut font_face in stylesheet.font_faces {
+	    font_face.sources = font_face.sources.iter().map(|source| resolve_url(url, source)).collect();
+	    font_faces.push(font_face);
+	}
+	Stylesheet{imports: Vec::new(), rules, font_faces}
+    }
+    // gets a linked or imported stylesheet, asking for it if it hasn't been loaded (it's empty until it arrives)
+    fn load_stylesheet(&mut self, url: &str) -> String {
+	match self.stylesheets.get(url) {
+	    Some(css) => css.to_string(),
+	    None => {
+		self.request(Resource::Stylesheet(url.to_string()));
+		String::new()
+	    },
+	}
+    }
+    // asks for a resource to be loaded, unless it already has been
+    fn request(&mut self, resource: Resource) {
+	if !self.pending.contains(&resource) {
+	    self.pending.push(resource.clone());
+	    self.requests.push(resource);
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::layout::Distance;
+    use crate::renderer::web::style::Display;
+
+    // the display of the first element a selector matches
+    fn display(loader: &DocLoader, selector: &str) -> Display {
+	let element = loader.parser.document().query_selector(selector).unwrap();
+	let display = element.style.borrow().display;
+	display
+    }
+
+    #[test]
+    fn stylesheets_are_asked_for_and_applied_when_they_arrive() {
+	let mut loader = DocLoader::new("file:///site/page.html");
+	loader.feed(b"<html><head><link rel=stylesheet href=a.css></head><body><p>x</p><span>y</span></body></html>");
+	loader.finish();
+	let a = Resource::Stylesheet(String::from("file:///site/a.css"));
+	assert!(loader.take_requests().contains(&a));
+	assert!(loader.is_loading());
+	assert_eq!(display(&loader, "span"), Display::Inline);
+	// imports are asked for once the stylesheet that has them arrives
+	loader.loaded(a, Some(b"@import 'css/b.css'; span { display: block }".to_vec()));
+	assert_eq!(display(&loader, "span"), Display::Block);
+	let b = Resource::Stylesheet(String::from("file:///site/css/b.css"));
+	assert_eq!(loader.take_requests(), std::slice::from_ref(&b));
+	loader.loaded(b, Some(b"p { display: none }".to_vec()));
+	assert_eq!(display(&loader, "p"), Display::None);
+	assert!(loader.take_requests().is_empty());
+	assert!(!loader.is_loading());
+    }
+
+    #[test]
+    fn default_stylesheet_parses_without_dropping_anything() {
+	let mut source = rules::DEFAULT_CSS.to_string();
+	while let Some(start) = source.find("/*") {
+	    let end = source[start..].find("*/").unwrap() + start + 2;
+	    source.replace_range(start..end, "");
+	}
+	let blocks = source.split('}').filter(|block| !block.trim().is_empty()).collect::<Vec<&str>>();
+	assert_eq!(css::parse(rules::DEFAULT_CSS.to_string(), Origin::UserAgent).rules.len(), blocks.len());
+	for block in blocks {
+	    let (selectors, declarations) = block.split_once('{').unwrap();
+	    for declaration in declarations.split(';').filter(|declaration| !declaration.trim().is_empty()) {
+		assert!(!css::parse_declaration_list(declaration).is_empty(), "{} {{ {} }}", selectors.trim(), declaration.trim());
+	    }
+	}
+    }
+
+    #[test]
+    fn default_stylesheet_applies() {
+	let mut loader = DocLoader::new("file:///page.html");
+	loader.feed(b"<ul id=outer><li><ul id=inner><li>x</ul></ul><p hidden>y</p>");
+	loader.finish();
+	let margin_top = |selector: &str| loader.parser.document().query_selector(selector).unwrap().style.borrow().margin_top;
+	// 1em
+	assert_eq!(margin_top("#outer"), Some(Distance::Absolute(rules::DEFAULT_FONT_SIZE as f64)));
+	assert_eq!(margin_top("#inner"), Some(Distance::Absolute(0.)));
+	assert_eq!(display(&loader, "p"), Display::None);
+	assert_eq!(display(&loader, "li"), Display::ListItem);
+    }
+
+    #[test]
+    fn user_stylesheet_goes_between_the_default_one_and_the_page() {
+	let mut loader = DocLoader::new("file:///page.html");
+	loader.user_stylesheet = Some(String::from("file:///user.css"));
+	loader.feed(b"<style>span { margin-top: 5px }</style><p>x</p><span>y</span>");
+	loader.finish();
+	let user = Resource::Stylesheet(String::from("file:///user.css"));
+	assert!(loader.take_requests().contains(&user));
+	loader.loaded(user, Some(b"p, span { margin-top: 3px; display: block }".to_vec()));
+	let margin_top = |selector: &str| loader.parser.document().query_selector(selector).unwrap().style.borrow().margin_top;
+	// the user's rules beat the default ones
+	assert_eq!(margin_top("p"), Some(Distance::Absolute(3.)));
+	// but the page's beat the user's
+	assert_eq!(margin_top("span"), Some(Distance::Absolute(5.)));
+	assert_eq!(display(&loader, "span"), Display::Block);
+    }
+
+    #[test]
+    fn relative_urls() {
+	let base = "http://example.com/a/b/page.html?q#f";
+	assert_eq!(resolve_url(base, "c.html"), "http://example.com/a/b/c.html");
+	assert_eq!(resolve_url(base, "./c.html"), "http://example.com/a/b/c.html");
+	assert_eq!(resolve_url(base, "../c.html"), "http://example.com/a/c.html");
+	assert_eq!(resolve_url(base, "../../../../c.html"), "http://example.com/c.html");
+	assert_eq!(resolve_url(base, "/c.html"), "http://example.com/c.html");
+	assert_eq!(resolve_url("http://example.com", "c.html"), "http://example.com/c.html");
+	// urls that are only a query or fragment keep the rest of the page's url
+	assert_eq!(resolve_url(base, "?x"), "http://example.com/a/b/page.html?x");
+	assert_eq!(resolve_url(base, "#x"), "http://example.com/a/b/page.html?q#x");
+	assert_eq!(resolve_url(base, ""), "http://example.com/a/b/page.html?q");
+	assert_eq!(resolve_url(base, "c.html?x#y"), "http://example.com/a/b/c.html?x#y");
+	// scheme-relative urls only take the scheme, and full urls nothing
+	assert_eq!(resolve_url(base, "//other.org/x"), "http://other.org/x");
+	assert_eq!(resolve_url(base, " https://other.org/x "), "https://other.org/x");
+	assert_eq!(resolve_url(base, "mailto:someone@example.com"), "mailto:someone@example.com");
+    }
+
+    #[test]
+    fn file_urls() {
+	assert_eq!(resolve_url("file:///site/dir/page.html", "../a.css"), "file:///site/a.css");
+	assert_eq!(resolve_url("file:///site/dir/page.html", "/a.css"), "file:///a.css");
+	assert_eq!(resolve_url("/site/page.html", "img/a.png"), "file:///site/img/a.png");
+	assert_eq!(resolve_url("/site/page.html", "//x/a.css"), "file://x/a.css");
+    }
+
+    #[test]
+    fn dot_segments() {
+	assert_eq!(remove_dot_segments("/a/b/c/./../../g"), "/a/g");
+	assert_eq!(remove_dot_segments("/a/./b/."), "/a/b/");
+	assert_eq!(remove_dot_segments("/a/b/.."), "/a/");
+	assert_eq!(remove_dot_segments("/../a"), "/a");
+	assert_eq!(remove_dot_segments("/a/.b/..c"), "/a/.b/..c");
+	// the query and fragment are left alone
+	assert_eq!(remove_dot_segments("/a/../b?x=/../#y/.."), "/b?x=/../#y/..");
+    }
+
+    #[test]
+    fn base_href_changes_what_urls_are_relative_to() {
+	let mut loader = DocLoader::new("http://example.com/dir/page.html");
+	loader.feed(b"<base href=../other/><link rel=stylesheet href=a.css><link rel=stylesheet href=//cdn.example.com/b.css>");
+	loader.finish();
+	let requests = loader.take_requests();
+	assert!(requests.contains(&Resource::Stylesheet(String::from("http://example.com/other/a.css"))));
+	assert!(requests.contains(&Resource::Stylesheet(String::from("http://cdn.example.com/b.css"))));
+	// and it can be a full url
+	let mut loader = DocLoader::new("file:///site/page.html");
+	loader.feed(b"<base href=http://example.com/><link rel=stylesheet href=a.css>");
+	loader.finish();
+	assert!(loader.take_requests().contains(&Resource::Stylesheet(String::from("http://example.com/a.css"))));
+    }
+
+    #[test]
+    fn typed_urls_match_links_to_them() {
+	assert_eq!(normalize_url("/site/./dir/../page.html"), resolve_url("file:///site/index.html", "page.html"));
+	assert_eq!(normalize_url(" http://example.com/a?b#c "), "http://example.com/a?b#c");
+	let mut loader = DocLoader::new("file:///site/index.html");
+	loader.set_history(&[normalize_url("/site/page.html")]);
+	loader.feed(b"<a href=page.html>visited</a><a href=other.html>not visited</a>");
+	loader.finish();
+	let visited = loader.parser.document().query_selector_all(":visited");
+	assert_eq!(visited.len(), 1);
+	assert_eq!(visited[0].get_attribute("href").as_deref(), Some("page.html"));
+    }
+
+    #[test]
+    fn resources_are_only_asked_for_once() {
+	let mut loader = DocLoader::new("http://example.com/");
+	loader.feed(b"<link rel=stylesheet href=/a.css><link rel=stylesheet href=/a.css>");
+	let requests = loader.take_requests();
+	loader.feed(b"<style>@import url(/a.css);</style>");
+	loader.finish();
+	let a = Resource::Stylesheet(String::from("http://example.com/a.css"));
+	assert_eq!(requests.iter().filter(|r| *r == &a).count(), 1);
+	assert!(!loader.take_requests().contains(&a));
+	// ones that couldn't be loaded aren't asked for again
+	loader.loaded(a.clone(), None);
+	loader.feed(b"");
+	assert!(!loader.take_requests().contains(&a));
     }
 }
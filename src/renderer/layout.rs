@@ -1,4 +1,4 @@
-use cairo::{FontSlant, FontWeight};
+use crate::renderer::web::style::FontStyle;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Distance {
@@ -8,6 +8,102 @@ pub enum Distance {
     Relative(f64),
     // a combination of the two
     Combo(f64, f64),
+    // clamp(min, value, max) with each as pixels and percent, which is how min() and max() are kept too
+    // (the min or max is infinite when there isn't one)
+    Clamp((f64, f64), (f64, f64), (f64, f64)),
+}
+
+impl Distance {
+    // the pixels and percent of a distance, if it doesn't have a min or max
+    fn linear(self) -> Option<(f64, f64)> {
+	match self {
+	    Distance::Absolute(pixels) => Some((pixels, 0.)),
+	    Distance::Relative(percent) => Some((0., percent)),
+	    Distance::Combo(pixels, percent) => Some((pixels, percent)),
+	    Distance::Clamp(..) => None,
+	}
+    }
+    // works out a distance in pixels, where percents are of size
+    pub fn to_pixels(self, size: f64) -> f64 {
+	let pixels = |(pixels, percent): (f64, f64)| pixels + percent * size;
+	match self {
+	    // if the min is bigger than the max, the min wins
+	    Distance::Clamp(min, value, max) => pixels(value).min(pixels(max)).max(pixels(min)),
+	    _ => pixels(self.linear().unwrap()),
+	}
+    }
+    // multiplies a distance by a number
+    pub fn scale(self, factor: f64) -> Distance {
+	let scale = |(pixels, percent): (f64, f64)| (pixels * factor, percent * factor);
+	match self {
+	    Distance::Absolute(pixels) => Distance::Absolute(pixels * factor),
+	    Distance::Relative(percent) => Distance::Relative(percent * factor),
+	    Distance::Combo(pixels, percent) => Distance::Combo(pixels * factor, percent * factor),
+	    // infinite bounds would turn into nan
+	    Distance::Clamp(..) if factor == 0. => Distance::Absolute(0.),
+	    Distance::Clamp(min, value, max) if factor > 0. => Distance::Clamp(scale(min), scale(value), scale(max)),
+	    // negative numbers swap the min and max (which is only exact if the min isn't bigger than the max)
+	    Distance::Clamp(min, value, max) => Distance::Clamp(scale(max), scale(value), scale(min)),
+	}
+    }
+    // the smallest of some distances, or None if it can't be kept as a distance
+    pub fn min(distances: &[Distance]) -> Option<Distance> {
+	match extremes(distances, |(pixels, percent), (other_pixels, other_percent)| pixels <= other_pixels && percent <= other_percent)?.as_slice() {
+	    [distance] => Some(Distance::from_linear(*distance)),
+	    [first, second] => Some(Distance::Clamp((f64::NEG_INFINITY, 0.), *first, *second)),
+	    _ => None,
+	}
+    }
+    // the largest of some distances, or None if it can't be kept as a distance
+    pub fn max(distances: &[Distance]) -> Option<Distance> {
+	match extremes(distances, |(pixels, percent), (other_pixels, other_percent)| pixels >= other_pixels && percent >= other_percent)?.as_slice() {
+	    [distance] => Some(Distance::from_linear(*distance)),
+	    [first, second] => Some(Distance::Clamp(*first, *second, (f64::INFINITY, 0.))),
+	    _ => None,
+	}
+    }
+    // a distance kept between a min and max, or None if it can't be kept as a distance
+    pub fn clamp(min: Distance, value: Distance, max: Distance) -> Option<Distance> {
+	Some(Distance::Clamp(min.linear()?, value.linear()?, max.linear()?))
+    }
+    // adds distances, or None if the sum can't be kept exactly (which is when both have a min or max)
+    pub fn checked_add(self, other: Distance) -> Option<Distance> {
+	match (self, other) {
+	    (Distance::Clamp(..), Distance::Clamp(..)) => None,
+	    _ => Some(self+other),
+	}
+    }
+    // multiplies a distance by a number, or None if it can't be kept exactly
+    // (which is when a negative number would swap a min and max that might be the wrong way around)
+    pub fn checked_scale(self, factor: f64) -> Option<Distance> {
+	match self {
+	    Distance::Clamp(min, _, max) if factor < 0. && !(min.0 <= max.0 && min.1 <= max.1) => None,
+	    _ => Some(self.scale(factor)),
+	}
+    }
+    fn from_linear((pixels, percent): (f64, f64)) -> Distance {
+	if percent == 0. {
+	    Distance::Absolute(pixels)
+	} else if pixels == 0. {
+	    Distance::Relative(percent)
+	} else {
+	    Distance::Combo(pixels, percent)
+	}
+    }
+}
+
+// drops distances that are always beaten by another one (percents are never negative, so 10px + 5% always beats 10px for max),
+// giving the ones left as pixels and percent
+fn extremes(distances: &[Distance], beats: impl Fn((f64, f64), (f64, f64)) -> bool) -> Option<Vec<(f64, f64)>> {
+    let linear = distances.iter().map(|distance| distance.linear()).collect::<Option<Vec<(f64, f64)>>>()?;
+    let mut left: Vec<(f64, f64)> = Vec::new();
+    for distance in linear {
+	if !left.iter().any(|other| beats(*other, distance)) {
+	    left.retain(|other| !beats(distance, *other));
+	    left.push(distance);
+	}
+    }
+    Some(left)
 }
 
 // add distances
@@ -15,12 +111,15 @@ impl std::ops::Add for Distance {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
+	// adding to a clamp moves its bounds along with its value
+	let shift = |(pixels, percent): (f64, f64), (other_pixels, other_percent): (f64, f64)| (pixels+other_pixels, percent+other_percent);
 	match self {
 	    Distance::Absolute(pixels) => {
 		match other {
 		    Distance::Absolute(other_pixels) => Distance::Absolute(pixels+other_pixels),
 		    Distance::Relative(other_percent) => Distance::Combo(pixels, other_percent),
 		    Distance::Combo(other_pixels, other_percent) => Distance::Combo(pixels+other_pixels, other_percent),
+		    Distance::Clamp(..) => other+self,
 		}
 	    },
 	    Distance::Relative(percent) => {
@@ -28,6 +127,7 @@ impl std::ops::Add for Distance {
 		    Distance::Absolute(other_pixels) => Distance::Combo(other_pixels, percent),
 		    Distance::Relative(other_percent) => Distance::Relative(percent+other_percent),
 		    Distance::Combo(other_pixels, other_percent) => Distance::Combo(other_pixels, percent+other_percent),
+		    Distance::Clamp(..) => other+self,
 		}
 	    },
 	    Distance::Combo(pixels, percent) => {
@@ -35,8 +135,14 @@ impl std::ops::Add for Distance {
 		    Distance::Absolute(other_pixels) => Distance::Combo(pixels+other_pixels, percent),
 		    Distance::Relative(other_percent) => Distance::Combo(pixels, percent+other_percent),
 		    Distance::Combo(other_pixels, other_percent) => Distance::Combo(pixels+other_pixels, percent+other_percent),
+		    Distance::Clamp(..) => other+self,
 		}
 	    },
+	    Distance::Clamp(min, value, max) => match other {
+		// two clamps can't be added exactly, so the other one is taken as its value (calc() uses checked_add so it never does this)
+		Distance::Clamp(_, (other_pixels, other_percent), _) => self+Distance::Combo(other_pixels, other_percent),
+		_ => other.linear().map_or(self, |other| Distance::Clamp(shift(min, other), shift(value, other), shift(max, other))),
+	    },
 	}
     }
 }
@@ -52,6 +158,7 @@ impl std::ops::Sub for Distance {
 		    Distance::Absolute(other_pixels) => Distance::Absolute(pixels-other_pixels),
 		    Distance::Relative(other_percent) => Distance::Combo(pixels, -other_percent),
 		    Distance::Combo(other_pixels, other_percent) => Distance::Combo(pixels-other_pixels, -other_percent),
+		    Distance::Clamp(..) => self+other.scale(-1.),
 		}
 	    },
 	    Distance::Relative(percent) => {
@@ -59,6 +166,7 @@ impl std::ops::Sub for Distance {
 		    Distance::Absolute(other_pixels) => Distance::Combo(-other_pixels, percent),
 		    Distance::Relative(other_percent) => Distance::Relative(percent-other_percent),
 		    Distance::Combo(other_pixels, other_percent) => Distance::Combo(-other_pixels, percent-other_percent),
+		    Distance::Clamp(..) => self+other.scale(-1.),
 		}
 	    },
 	    Distance::Combo(pixels, percent) => {
@@ -66,8 +174,10 @@ impl std::ops::Sub for Distance {
 		    Distance::Absolute(other_pixels) => Distance::Combo(pixels-other_pixels, percent),
 		    Distance::Relative(other_percent) => Distance::Combo(pixels, percent-other_percent),
 		    Distance::Combo(other_pixels, other_percent) => Distance::Combo(pixels-other_pixels, percent-other_percent),
+		    Distance::Clamp(..) => self+other.scale(-1.),
 		}
 	    },
+	    Distance::Clamp(..) => self+other.scale(-1.),
 	}
     }
 }
@@ -86,33 +196,6 @@ impl std::ops::SubAssign for Distance {
     }
 }
 
-// get distance from string
-impl From<String> for Distance {
-    fn from(s: String) -> Distance {
-	if s.ends_with("em") {
-	    // TODO: account for dpi
-	    Distance::Absolute(s.trim_end_matches("em").trim().parse::<f64>().unwrap()*crate::rules::DEFAULT_FONT_SIZE as f64)
-	} else if s.ends_with("%") {
-	    Distance::Relative(s.trim_end_matches('%').trim().parse::<f64>().unwrap()/100.)
-	} else if s.ends_with("px") {
-	    Distance::Absolute(s.trim_end_matches("px").trim().parse::<f64>().unwrap())
-	} else if s == "auto" {
-	    // placeholder
-	    Distance::Relative(0.333)
-	} else if s == "0" {
-	    Distance::Absolute(0.)
-	} else {
-	    panic!("This unit is not implemented yet!: {}", s);
-	}
-    }
-}
-
-impl From<&str> for Distance {
-    fn from(s: &str) -> Distance {
-	Distance::from(s.to_string())
-    }
-}
-
 // box with size data
 pub struct LayoutBox {
     pub height: Option<Distance>,
@@ -120,6 +203,8 @@ pub struct LayoutBox {
     pub visual_width: Distance,
     pub visual_height: Option<Distance>,
     pub content: Content,
+    // where the box was last drawn, as x, y, width and height in pixels
+    pub bounds: [f64;4],
 }
 
 impl LayoutBox {
@@ -128,13 +213,14 @@ impl LayoutBox {
 		  height: None,
 		  visual_width: Distance::Absolute(0.),
 		  visual_height: None,
-		  content: Content::Solid(Block::new())}
+		  content: Content::Solid(Box::new(Block::new())),
+		  bounds: [0.0;4]}
     }
 }
 
-// content of box
+// content of box (blocks are boxed since they're much bigger than labels)
 pub enum Content {
-    Solid(Block),
+    Solid(Box<Block>),
     Text(Label)
 }
 
@@ -170,6 +256,10 @@ pub struct Label {
     pub text: String,
     pub font_size: Distance,
     pub font_color: [f64;4],
-    pub weight: FontWeight,
-    pub slant: FontSlant,
+    // families to look for a font in
+    pub font_family: Vec<String>,
+    pub weight: u16,
+    pub style: FontStyle,
+    // whether text wraps when it reaches the end of the line
+    pub wrap: bool,
 }
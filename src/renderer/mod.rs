@@ -2,26 +2,118 @@ pub mod layout;
 pub mod web;
 
 use std::rc::Rc;
+use std::cell::{Cell, RefCell};
 use layout::Distance;
+use web::css::StyleRule;
+use web::css::media::Device;
+use web::font::Fonts;
+use web::html::Node;
 
 use cairo::{Path, Context};
 
 pub enum Doc {
     Blank,
-    Web(Rc<web::html::Node>),
+    // document, the css rules from its stylesheets, the screen it's shown on, and the fonts its stylesheets loaded
+    Web(Rc<Node>, RefCell<Vec<StyleRule>>, Cell<Device>, Rc<RefCell<Fonts>>),
 }
 
 impl Doc {
     pub fn draw(&self, cr: &Context, width: i32, height: i32) -> Vec<(Path, [f64;4])> {
 	match &self {
 	    Doc::Blank => Vec::new(),
-	    Doc::Web(node) => web::render::draw_node(cr, Rc::clone(node), Distance::Absolute(0.), Distance::Absolute(0.), width, height),
+	    Doc::Web(node, _, _, fonts) => web::render::draw_node(cr, node, &fonts.borrow(), Distance::Absolute(0.), Distance::Absolute(0.), width, height),
 	}
     }
     pub fn render(&self) {
+	self.restyle();
 	match &self {
 	    Doc::Blank => {},
-	    Doc::Web(node) => web::render::render_node(Rc::clone(&node), Distance::Relative(1.), Distance::Relative(1.)),
+	    Doc::Web(node, _, _, _) => web::render::render_node(node, Distance::Relative(1.), Distance::Relative(1.)),
 	}
     }
+    // applies css to whatever changed since it was last applied
+    pub fn restyle(&self) {
+	if let Doc::Web(node, css_rules, device, _) = &self {
+	    let css_rules = css_rules.borrow();
+	    // rules only apply if their media queries match the screen
+	    let css_rules = css_rules.iter().filter(|rule| rule.media_applies(&device.get())).collect::<Vec<&StyleRule>>();
+	    web::html::apply_css(&css_rules, node, &device.get());
+	}
+    }
+    // changes the screen the document is shown on, returning whether it has to be styled again
+    // (which is when it changes size, since vw and vh depend on that, or when media queries change whether they match)
+    pub fn set_device(&self, new_device: Device) -> bool {
+	match &self {
+	    Doc::Blank => false,
+	    Doc::Web(node, css_rules, device, _) => {
+		let old_device = device.replace(new_device);
+		let resized = (old_device.width, old_device.height) != (new_device.width, new_device.height);
+		let changed = resized || css_rules.borrow().iter().any(|rule| rule.media_applies(&old_device) != rule.media_applies(&new_device));
+		if changed {
+		    node.invalidate_style();
+		}
+		changed
+	    },
+	}
+    }
+    // finds the element drawn at a point
+    pub fn element_at(&self, x: f64, y: f64) -> Option<Rc<Node>> {
+	match &self {
+	    Doc::Blank => None,
+	    Doc::Web(node, _, _, _) => web::render::element_at(node, x, y),
+	}
+    }
+    // moves the pointer to a point (or off of the page), returning whether the page needs to be redrawn
+    pub fn pointer_moved(&self, point: Option<(f64, f64)>) -> bool {
+	match &self {
+	    Doc::Blank => false,
+	    Doc::Web(node, _, _, _) => node.set_hovered(point.and_then(|(x, y)| self.element_at(x, y)).as_ref()),
+	}
+    }
+    // presses the pointer down at a point, which activates and focuses what's there
+    pub fn pointer_pressed(&self, x: f64, y: f64) -> bool {
+	match &self {
+	    Doc::Blank => false,
+	    Doc::Web(node, _, _, _) => {
+		let element = self.element_at(x, y);
+		// focus goes to the closest thing that can have it
+		let mut focused = element.clone();
+		while let Some(e) = focused.as_ref().filter(|e| !e.is_focusable()) {
+		    focused = e.parent().filter(|p| p.is_element());
+		}
+		let active_changed = node.set_active(element.as_ref());
+		let focus_changed = node.set_focused(focused.as_ref());
+		active_changed || focus_changed
+	    },
+	}
+    }
+    // lets go of the pointer
+    pub fn pointer_released(&self) -> bool {
+	match &self {
+	    Doc::Blank => false,
+	    Doc::Web(node, _, _, _) => node.set_active(None),
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web::css::{self, cascade::Origin};
+    use web::style::Display;
+
+    #[test]
+    fn media_queries_are_checked_again_when_the_screen_changes() {
+	let document = web::html::parse(String::from("<html><body><span>a</span></body></html>"));
+	let css = css::parse(String::from("@media (max-width: 600px) { span { display: block } }"), Origin::Author);
+	let doc = Doc::Web(Rc::clone(&document), RefCell::new(css.rules), Cell::new(Device::default()), Rc::new(RefCell::new(Fonts::default())));
+	doc.restyle();
+	let span = document.query_selector("span").unwrap();
+	assert_eq!(span.style.borrow().display, Display::Inline);
+	assert!(doc.set_device(Device{width: 500., ..Device::default()}));
+	doc.restyle();
+	assert_eq!(span.style.borrow().display, Display::Block);
+	// nothing has to be done when nothing changes
+	assert!(!doc.set_device(Device{width: 500., ..Device::default()}));
+    }
 }
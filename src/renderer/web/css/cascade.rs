@@ -0,0 +1,113 @@
+use super::{serialize, Declaration};
+use super::selector::Specificity;
+
+use std::collections::HashMap;
+
+// where css comes from, from least to most important for normal declarations
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Origin {
+    // the browser's defaults
+    UserAgent,
+    // the person using the browser
+    User,
+    // the page
+    Author,
+}
+
+// a declaration that applies to an element, and what decides whether it wins
+#[derive(Debug, Clone, PartialEq)]
+pub struct CascadedDeclaration<'a> {
+    pub declaration: &'a Declaration,
+    pub origin: Origin,
+    // whether it's from a style attribute (which beats any selector)
+    pub inline: bool,
+    pub specificity: Specificity,
+}
+impl CascadedDeclaration<'_> {
+    // declarations that sort higher win
+    fn priority(&self) -> (u8, bool, Specificity) {
+	// !important turns the order of origins around, so the user and browser can override the page
+	let origin = match self.declaration.important {
+	    false => self.origin as u8,
+	    true => 5 - self.origin as u8,
+	};
+	(origin, self.inline, self.specificity)
+    }
+}
+
+// picks the winning value of each property, with declarations given in the order they are written in
+pub fn cascade(mut declarations: Vec<CascadedDeclaration>) -> HashMap<String, Declaration> {
+    // the sort is stable, so when everything else is the same the declaration written last wins
+    declarations.sort_by_key(|d| d.priority());
+    let mut values = HashMap::new();
+    // properties that were reverted, and the origin they were reverted in
+    let mut reverted: HashMap<&str, Origin> = HashMap::new();
+    for d in declarations.iter().rev() {
+	let name = d.declaration.name.as_str();
+	if values.contains_key(name) || reverted.get(name).is_some_and(|origin| d.origin >= *origin) {
+	    continue;
+	}
+	// revert goes back to what the origins before this one say (or unset if there aren't any)
+	if serialize(&d.declaration.value).trim().eq_ignore_ascii_case("revert") {
+	    reverted.insert(name, d.origin);
+	    continue;
+	}
+	values.insert(name.to_string(), d.declaration.clone());
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_declaration_list;
+
+    // the winning value of a property among declarations from different places
+    fn winner(declarations: &[(&str, Origin, bool, Specificity)]) -> Option<String> {
+	let parsed: Vec<Declaration> = declarations.iter().map(|(css, ..)| parse_declaration_list(css).remove(0)).collect();
+	let cascaded = parsed.iter().zip(declarations).map(|(declaration, (_, origin, inline, specificity))| {
+	    CascadedDeclaration{declaration, origin: *origin, inline: *inline, specificity: *specificity}
+	}).collect();
+	cascade(cascaded).remove("color").map(|declaration| serialize(&declaration.value))
+    }
+
+    #[test]
+    fn later_declarations_win() {
+	let s = Specificity(0, 1, 0);
+	assert_eq!(winner(&[("color: red", Origin::Author, false, s), ("color: blue", Origin::Author, false, s)]), Some("blue".to_string()));
+    }
+
+    #[test]
+    fn more_specific_declarations_win() {
+	assert_eq!(winner(&[("color: red", Origin::Author, false, Specificity(1, 0, 0)),
+			    ("color: blue", Origin::Author, false, Specificity(0, 5, 5))]), Some("red".to_string()));
+	// style attributes beat any selector
+	assert_eq!(winner(&[("color: red", Origin::Author, true, Specificity::default()),
+			    ("color: blue", Origin::Author, false, Specificity(9, 0, 0))]), Some("red".to_string()));
+    }
+
+    #[test]
+    fn origins() {
+	let s = Specificity::default();
+	assert_eq!(winner(&[("color: red", Origin::Author, false, s), ("color: blue", Origin::User, false, Specificity(1, 0, 0)),
+			    ("color: green", Origin::UserAgent, false, s)]), Some("red".to_string()));
+	// !important turns the order around
+	assert_eq!(winner(&[("color: red !important", Origin::Author, true, s), ("color: blue !important", Origin::User, false, s)]), Some("blue".to_string()));
+	assert_eq!(winner(&[("color: red !important", Origin::UserAgent, false, s), ("color: blue !important", Origin::User, false, s)]), Some("red".to_string()));
+	assert_eq!(winner(&[("color: red !important", Origin::Author, false, s), ("color: blue", Origin::Author, true, s)]), Some("red".to_string()));
+    }
+
+    #[test]
+    fn revert_goes_back_to_earlier_origins() {
+	let s = Specificity::default();
+	assert_eq!(winner(&[("color: red", Origin::UserAgent, false, s), ("color: blue", Origin::Author, false, s),
+			    ("color: revert", Origin::Author, true, s)]), Some("red".to_string()));
+	assert_eq!(winner(&[("color: red", Origin::UserAgent, false, s), ("color: blue", Origin::User, false, s),
+			    ("color: revert", Origin::Author, false, s)]), Some("blue".to_string()));
+	// without anything to go back to, it's like unset
+	assert_eq!(winner(&[("color: blue", Origin::Author, false, s), ("color: revert", Origin::Author, false, s)]), None);
+	// reverting in the browser's stylesheet leaves nothing
+	assert_eq!(winner(&[("color: red", Origin::UserAgent, false, s), ("color: revert", Origin::UserAgent, false, s)]), None);
+    }
+}
+
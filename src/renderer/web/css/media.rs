@@ -0,0 +1,458 @@
+use super::ComponentValue;
+use super::tokenizer::{self, Token};
+use crate::rules;
+
+// what media queries are checked against
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Device {
+    // size of the viewport in pixels
+    pub width: f64,
+    pub height: f64,
+    // device pixels per css pixel
+    pub resolution: f64,
+    // whether the user wants a dark color scheme
+    pub dark: bool,
+    // whether this is for printing instead of a screen
+    pub print: bool,
+}
+impl Default for Device {
+    fn default() -> Device {
+	Device{width: 1024., height: 768., resolution: 1., dark: false, print: false}
+    }
+}
+impl Device {
+    // the value of a media feature for this device
+    fn feature(&self, name: &str) -> Option<MediaValue> {
+	Some(match name {
+	    "width" => MediaValue::Number(self.width),
+	    "height" => MediaValue::Number(self.height),
+	    "aspect-ratio" => MediaValue::Number(self.width/self.height),
+	    "orientation" if self.height >= self.width => MediaValue::Ident(String::from("portrait")),
+	    "orientation" => MediaValue::Ident(String::from("landscape")),
+	    "resolution" => MediaValue::Number(self.resolution),
+	    "prefers-color-scheme" if self.dark => MediaValue::Ident(String::from("dark")),
+	    "prefers-color-scheme" => MediaValue::Ident(String::from("light")),
+	    // bits per color channel
+	    "color" => MediaValue::Number(8.),
+	    "monochrome" | "grid" => MediaValue::Number(0.),
+	    "hover" | "any-hover" => MediaValue::Ident(String::from("hover")),
+	    "pointer" | "any-pointer" => MediaValue::Ident(String::from("fine")),
+	    _ => return None,
+	})
+    }
+}
+
+// a comma separated list of media queries, which matches if any of them do (or if it's empty)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MediaQueryList(pub Vec<MediaQuery>);
+impl MediaQueryList {
+    pub fn matches(&self, device: &Device) -> bool {
+	self.0.is_empty() || self.0.iter().any(|query| query.matches(device))
+    }
+}
+
+// a media query like "not print and (min-width: 600px)"
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaQuery {
+    pub negated: bool,
+    // screen, print or all (None is the same as all)
+    pub media_type: Option<String>,
+    pub condition: Option<MediaCondition>,
+}
+impl MediaQuery {
+    // queries that can't be parsed never match
+    fn never() -> MediaQuery {
+	MediaQuery{negated: true, media_type: Some(String::from("all")), condition: None}
+    }
+    pub fn matches(&self, device: &Device) -> bool {
+	let media_type = match self.media_type.as_deref() {
+	    None | Some("all") => true,
+	    Some("screen") => !device.print,
+	    Some("print") => device.print,
+	    _ => false,
+	};
+	let condition = self.condition.as_ref().is_none_or(|condition| condition.matches(device));
+	(media_type && condition) != self.negated
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaCondition {
+    // (name: value), (name), or a range like (width >= 600px)
+    Feature(MediaFeature),
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+    // something in parentheses that isn't understood, which is unknown instead of true or false (so not doesn't make it match)
+    Unknown,
+}
+impl MediaCondition {
+    pub fn matches(&self, device: &Device) -> bool {
+	self.evaluate(device) == Some(true)
+    }
+    // whether the condition is true, or None if it can't be known (which not doesn't change)
+    fn evaluate(&self, device: &Device) -> Option<bool> {
+	match self {
+	    MediaCondition::Feature(feature) => feature.evaluate(device),
+	    MediaCondition::Not(condition) => condition.evaluate(device).map(|matches| !matches),
+	    // false and unknown is false, and true or unknown is true
+	    MediaCondition::And(conditions) => {
+		let results = conditions.iter().map(|c| c.evaluate(device)).collect::<Vec<_>>();
+		if results.contains(&Some(false)) {
+		    Some(false)
+		} else if results.contains(&None) {
+		    None
+		} else {
+		    Some(true)
+		}
+	    },
+	    MediaCondition::Or(conditions) => {
+		let results = conditions.iter().map(|c| c.evaluate(device)).collect::<Vec<_>>();
+		if results.contains(&Some(true)) {
+		    Some(true)
+		} else if results.contains(&None) {
+		    None
+		} else {
+		    Some(false)
+		}
+	    },
+	    MediaCondition::Unknown => None,
+	}
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaFeature {
+    // (color), which matches if the feature isn't 0 or none
+    Boolean(String),
+    // (orientation: portrait)
+    Plain(String, MediaValue),
+    // (width >= 600px), with min- and max- turned into ranges too
+    Range(String, Comparison, MediaValue),
+}
+impl MediaFeature {
+    pub fn matches(&self, device: &Device) -> bool {
+	self.evaluate(device) == Some(true)
+    }
+    // whether the feature matches, or None for features that aren't known
+    fn evaluate(&self, device: &Device) -> Option<bool> {
+	match self {
+	    MediaFeature::Boolean(name) => match device.feature(name)? {
+		MediaValue::Number(n) => Some(n != 0.),
+		MediaValue::Ident(ident) => Some(ident != "none"),
+	    },
+	    MediaFeature::Plain(name, value) => Some(&device.feature(name)? == value),
+	    MediaFeature::Range(name, comparison, MediaValue::Number(value)) => match device.feature(name)? {
+		MediaValue::Number(actual) => Some(match comparison {
+		    Comparison::Less => actual < *value,
+		    Comparison::LessOrEqual => actual <= *value,
+		    Comparison::Equal => actual == *value,
+		    Comparison::GreaterOrEqual => actual >= *value,
+		    Comparison::Greater => actual > *value,
+		}),
+		_ => None,
+	    },
+	    MediaFeature::Range(..) => None,
+	}
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+impl Comparison {
+    // the same comparison with the sides swapped, so 600px < width is width > 600px
+    fn flip(self) -> Comparison {
+	match self {
+	    Comparison::Less => Comparison::Greater,
+	    Comparison::LessOrEqual => Comparison::GreaterOrEqual,
+	    Comparison::Equal => Comparison::Equal,
+	    Comparison::GreaterOrEqual => Comparison::LessOrEqual,
+	    Comparison::Greater => Comparison::Less,
+	}
+    }
+}
+
+// values in media features, with lengths in pixels, resolutions in dppx and ratios divided out
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaValue {
+    Number(f64),
+    Ident(String),
+}
+
+// parses a media query list, like in a media attribute
+pub fn parse(media: &str) -> MediaQueryList {
+    parse_media_query_list(&super::component_values(&tokenizer::tokenize(media)))
+}
+
+pub fn parse_media_query_list(values: &[ComponentValue]) -> MediaQueryList {
+    let values = without_whitespace(values);
+    if values.is_empty() {
+	return MediaQueryList::default();
+    }
+    MediaQueryList(values.split(|v| v == &ComponentValue::Token(Token::Comma))
+		   .map(|query| parse_media_query(query).unwrap_or_else(MediaQuery::never))
+		   .collect())
+}
+
+fn parse_media_query(values: &[ComponentValue]) -> Option<MediaQuery> {
+    let mut values = values;
+    let mut negated = false;
+    match values.first() {
+	Some(ComponentValue::Token(Token::Ident(ident))) if ident.eq_ignore_ascii_case("only") => values = &values[1..],
+	// "not" before a media type negates the whole query, but "not (...)" is a condition
+	Some(ComponentValue::Token(Token::Ident(ident))) if ident.eq_ignore_ascii_case("not") && matches!(values.get(1), Some(ComponentValue::Token(Token::Ident(_)))) => {
+	    negated = true;
+	    values = &values[1..];
+	},
+	_ => {},
+    }
+    match values.first()? {
+	ComponentValue::Token(Token::Ident(media_type)) if !media_type.eq_ignore_ascii_case("not") => {
+	    let media_type = Some(media_type.to_ascii_lowercase());
+	    let condition = match values.get(1) {
+		None => None,
+		// conditions after a media type can only be joined with and (unless the or is in parentheses)
+		Some(ComponentValue::Token(Token::Ident(and))) if and.eq_ignore_ascii_case("and") => {
+		    if values[2..].iter().any(|v| matches!(v, ComponentValue::Token(Token::Ident(or)) if or.eq_ignore_ascii_case("or"))) {
+			return None;
+		    }
+		    Some(parse_condition(&values[2..])?)
+		},
+		_ => return None,
+	    };
+	    Some(MediaQuery{negated, media_type, condition})
+	},
+	_ if negated => None,
+	_ => Some(MediaQuery{negated, media_type: None, condition: Some(parse_condition(values)?)}),
+    }
+}
+
+// parses conditions like "(a) and (b)", "(a) or (b)" and "not (a)"
+fn parse_condition(values: &[ComponentValue]) -> Option<MediaCondition> {
+    if let Some(ComponentValue::Token(Token::Ident(not))) = values.first() {
+	if not.eq_ignore_ascii_case("not") {
+	    return match &values[1..] {
+		[value] => Some(MediaCondition::Not(Box::new(parse_in_parens(value)?))),
+		_ => None,
+	    };
+	}
+    }
+    let mut conditions = vec![parse_in_parens(values.first()?)?];
+    // and and or can't be mixed without parentheses
+    let mut joiner: Option<String> = None;
+    let mut rest = &values[1..];
+    while let [ComponentValue::Token(Token::Ident(word)), value, tail @ ..] = rest {
+	let word = word.to_ascii_lowercase();
+	if (word != "and" && word != "or") || joiner.as_ref().is_some_and(|j| *j != word) {
+	    return None;
+	}
+	conditions.push(parse_in_parens(value)?);
+	joiner = Some(word);
+	rest = tail;
+    }
+    if !rest.is_empty() {
+	return None;
+    }
+    Some(match joiner.as_deref() {
+	None => conditions.remove(0),
+	Some("and") => MediaCondition::And(conditions),
+	_ => MediaCondition::Or(conditions),
+    })
+}
+
+// parses a feature or condition in parentheses
+fn parse_in_parens(value: &ComponentValue) -> Option<MediaCondition> {
+    let contents = match value {
+	ComponentValue::Block('(', contents) => without_whitespace(contents),
+	// functions are allowed, but nothing understands them
+	ComponentValue::Function(..) => return Some(MediaCondition::Unknown),
+	_ => return None,
+    };
+    let nested = match contents.first() {
+	Some(ComponentValue::Block('(', _)) => true,
+	Some(ComponentValue::Token(Token::Ident(not))) => not.eq_ignore_ascii_case("not"),
+	_ => false,
+    };
+    let condition = if nested {
+	parse_condition(&contents)
+    } else {
+	parse_feature(&contents)
+    };
+    Some(condition.unwrap_or(MediaCondition::Unknown))
+}
+
+// parses what's inside of the parentheses of a media feature
+fn parse_feature(values: &[ComponentValue]) -> Option<MediaCondition> {
+    let feature = |name: &str| name.to_ascii_lowercase();
+    match values {
+	[ComponentValue::Token(Token::Ident(name))] => Some(MediaCondition::Feature(MediaFeature::Boolean(feature(name)))),
+	[ComponentValue::Token(Token::Ident(name)), ComponentValue::Token(Token::Colon), value @ ..] => {
+	    let name = feature(name);
+	    let value = parse_value(value)?;
+	    Some(MediaCondition::Feature(match (name.strip_prefix("min-"), name.strip_prefix("max-")) {
+		(Some(name), _) => MediaFeature::Range(name.to_string(), Comparison::GreaterOrEqual, value),
+		(_, Some(name)) => MediaFeature::Range(name.to_string(), Comparison::LessOrEqual, value),
+		_ => MediaFeature::Plain(name, value),
+	    }))
+	},
+	_ => {
+	    // ranges, like "width >= 600px", "600px < width" or "400px <= width <= 700px"
+	    let (parts, comparisons) = split_comparisons(values)?;
+	    let range = |name: &[ComponentValue], comparison: Comparison, value: &[ComponentValue]| -> Option<MediaCondition> {
+		match name {
+		    [ComponentValue::Token(Token::Ident(name))] => Some(MediaCondition::Feature(MediaFeature::Range(feature(name), comparison, parse_value(value)?))),
+		    _ => None,
+		}
+	    };
+	    match (parts.as_slice(), comparisons.as_slice()) {
+		([name @ [ComponentValue::Token(Token::Ident(_))], value], [comparison]) => range(name, *comparison, value),
+		([value, name], [comparison]) => range(name, comparison.flip(), value),
+		([low, name, high], [first, second]) => {
+		    Some(MediaCondition::And(vec![range(name, first.flip(), low)?, range(name, *second, high)?]))
+		},
+		_ => None,
+	    }
+	},
+    }
+}
+
+// splits a range into its parts and the comparisons between them
+fn split_comparisons(values: &[ComponentValue]) -> Option<(Vec<&[ComponentValue]>, Vec<Comparison>)> {
+    let mut parts = Vec::new();
+    let mut comparisons = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+    while index < values.len() {
+	let comparison = match &values[index] {
+	    ComponentValue::Token(Token::Delim(c @ ('<' | '>' | '='))) => {
+		let or_equal = *c != '=' && values.get(index+1) == Some(&ComponentValue::Token(Token::Delim('=')));
+		match (c, or_equal) {
+		    ('<', false) => Comparison::Less,
+		    ('<', true) => Comparison::LessOrEqual,
+		    ('>', false) => Comparison::Greater,
+		    ('>', true) => Comparison::GreaterOrEqual,
+		    _ => Comparison::Equal,
+		}
+	    },
+	    _ => {
+		index += 1;
+		continue;
+	    },
+	};
+	parts.push(&values[start..index]);
+	comparisons.push(comparison);
+	index += if comparison == Comparison::LessOrEqual || comparison == Comparison::GreaterOrEqual { 2 } else { 1 };
+	start = index;
+    }
+    parts.push(&values[start..]);
+    if comparisons.is_empty() || parts.iter().any(|part| part.is_empty()) {
+	return None;
+    }
+    Some((parts, comparisons))
+}
+
+// parses the value of a media feature
+fn parse_value(values: &[ComponentValue]) -> Option<MediaValue> {
+    match values {
+	[ComponentValue::Token(Token::Ident(ident))] => Some(MediaValue::Ident(ident.to_ascii_lowercase())),
+	[ComponentValue::Token(Token::Number(n, _))] => Some(MediaValue::Number(*n)),
+	// ratios like 16/9
+	[ComponentValue::Token(Token::Number(a, _)), ComponentValue::Token(Token::Delim('/')), ComponentValue::Token(Token::Number(b, _))] => Some(MediaValue::Number(a/b)),
+	[ComponentValue::Token(Token::Dimension(n, _, unit))] => {
+	    let unit = unit.to_ascii_lowercase();
+	    let scale = match unit.as_str() {
+		"px" => 1.,
+		// em in media queries is the default font size, since there isn't an element to be relative to
+		"em" | "rem" => rules::DEFAULT_FONT_SIZE as f64,
+		"in" => 96.,
+		"cm" => 96./2.54,
+		"mm" => 96./25.4,
+		"q" => 96./101.6,
+		"pt" => 96./72.,
+		"pc" => 16.,
+		// resolutions are in dots per pixel
+		"dppx" | "x" => 1.,
+		"dpi" => 1./96.,
+		"dpcm" => 2.54/96.,
+		_ => return None,
+	    };
+	    Some(MediaValue::Number(n*scale))
+	},
+	_ => None,
+    }
+}
+
+fn without_whitespace(values: &[ComponentValue]) -> Vec<ComponentValue> {
+    values.iter().filter(|v| v != &&ComponentValue::Token(Token::Whitespace)).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(media: &str, device: &Device) -> bool {
+	parse(media).matches(device)
+    }
+
+    #[test]
+    fn media_types() {
+	let screen = Device::default();
+	let print = Device{print: true, ..Device::default()};
+	assert!(matches("", &screen));
+	assert!(matches("all", &print));
+	assert!(matches("screen", &screen) && !matches("screen", &print));
+	assert!(matches("not screen", &print) && !matches("not screen", &screen));
+	assert!(matches("only print", &print));
+	assert!(!matches("tv", &screen));
+	// a list matches if any of its queries do
+	assert!(matches("print, (min-width: 100px)", &screen));
+    }
+
+    #[test]
+    fn width_and_height() {
+	let device = Device{width: 600., height: 800., ..Device::default()};
+	assert!(matches("(max-width: 600px)", &device));
+	assert!(!matches("(min-width: 601px)", &device));
+	assert!(matches("(width: 600px)", &device));
+	assert!(matches("(max-width: 50em)", &device) && !matches("(max-width: 49em)", &device));
+	assert!(matches("(width < 601px) and (height >= 800px)", &device));
+	assert!(matches("(500px < width <= 600px)", &device));
+	assert!(matches("(700px > width)", &device));
+	assert!(matches("(orientation: portrait)", &device));
+	assert!(matches("(max-aspect-ratio: 3/4)", &device));
+	assert!(!matches("(min-aspect-ratio: 16/9)", &device));
+    }
+
+    #[test]
+    fn resolution_and_color_scheme() {
+	let device = Device{resolution: 2., dark: true, ..Device::default()};
+	assert!(matches("(min-resolution: 2dppx)", &device));
+	assert!(matches("(min-resolution: 192dpi)", &device));
+	assert!(!matches("(min-resolution: 3x)", &device));
+	assert!(matches("(prefers-color-scheme: dark)", &device));
+	assert!(!matches("(prefers-color-scheme: dark)", &Device::default()));
+	assert!(matches("(color)", &device) && !matches("(monochrome)", &device));
+    }
+
+    #[test]
+    fn conditions() {
+	let device = Device::default();
+	assert!(matches("not (max-width: 100px)", &device));
+	assert!(matches("(max-width: 100px) or (color)", &device));
+	assert!(matches("screen and ((max-width: 100px) or (color))", &device));
+	assert!(matches("(not (max-width: 100px)) and (color)", &device));
+	// and and or can't be mixed, and media types can only be followed by and
+	assert!(!matches("(color) and (color) or (color)", &device));
+	assert!(!matches("screen or (color)", &device));
+	// features that aren't known never match, even when negated
+	assert!(!matches("(unknown-feature)", &device));
+	assert!(!matches("not (unknown: 1)", &device));
+	assert!(!matches("(width: 100qq)", &device));
+    }
+}
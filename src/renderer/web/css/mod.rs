@@ -0,0 +1,387 @@
+pub mod tokenizer;
+pub mod selector;
+pub mod cascade;
+pub mod media;
+pub mod shorthand;
+
+use tokenizer::Token;
+use selector::Selector;
+use cascade::Origin;
+use media::{Device, MediaQueryList};
+use super::font::{self, FontFaceRule};
+use super::style;
+
+// tokens grouped into blocks and functions
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComponentValue {
+    Token(Token),
+    // {}, [] or () block, with the character that opened it
+    Block(char, Vec<ComponentValue>),
+    // function name and arguments
+    Function(String, Vec<ComponentValue>),
+}
+// writes component values back out as css
+impl std::fmt::Display for ComponentValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    ComponentValue::Token(token) => write!(f, "{}", token),
+	    ComponentValue::Block(open, values) => {
+		let close = match open {
+		    '{' => '}',
+		    '[' => ']',
+		    _ => ')',
+		};
+		write!(f, "{}{}{}", open, serialize(values), close)
+	    },
+	    ComponentValue::Function(name, values) => write!(f, "{}({})", name, serialize(values)),
+	}
+    }
+}
+
+// a rule in a stylesheet
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    // selector and what's inside of the {}
+    Qualified(Vec<ComponentValue>, Vec<ComponentValue>),
+    // name (without the @), what comes before the block, and the block if there is one
+    At(String, Vec<ComponentValue>, Option<Vec<ComponentValue>>),
+}
+
+// a rule with selectors, and the declarations that apply to the elements they match
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleRule {
+    pub selectors: Vec<Selector>,
+    pub declarations: Vec<Declaration>,
+    pub origin: Origin,
+    // media queries from @media rules (and media attributes) that all have to match for it to apply
+    pub media: Vec<MediaQueryList>,
+}
+impl StyleRule {
+    // whether the rule's media queries match a device
+    pub fn media_applies(&self, device: &Device) -> bool {
+	self.media.iter().all(|media| media.matches(device))
+    }
+}
+
+// a stylesheet, with what it needs to have loaded
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stylesheet {
+    // stylesheets from @import rules, whose rules go before the ones in this one
+    pub imports: Vec<Import>,
+    pub rules: Vec<StyleRule>,
+    pub font_faces: Vec<FontFaceRule>,
+}
+
+// @import url(...) media;
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub url: String,
+    // the imported stylesheet only applies when this matches
+    pub media: MediaQueryList,
+}
+
+// property: value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: String,
+    pub value: Vec<ComponentValue>,
+    // whether it ended with !important
+    pub important: bool,
+    // the shorthand this is a longhand of, when the shorthand had var()s so its value couldn't be split up yet
+    // (the value is then the whole value of the shorthand)
+    pub shorthand: Option<String>,
+}
+
+// css parser
+pub fn parse(css: String, origin: Origin) -> Stylesheet {
+    let mut stylesheet = Stylesheet::default();
+    let mut rules = parse_stylesheet(&css).into_iter().peekable();
+    // @import has to come before everything else (except @charset)
+    while let Some(Rule::At(name, prelude, None)) = rules.peek() {
+	if name.eq_ignore_ascii_case("import") {
+	    stylesheet.imports.extend(parse_import(prelude));
+	} else if !name.eq_ignore_ascii_case("charset") {
+	    break;
+	}
+	rules.next();
+    }
+    add_rules(&mut stylesheet, rules.collect(), origin, &[]);
+    stylesheet
+}
+// adds rules to a stylesheet, with the media queries of the @media rules they are inside of
+fn add_rules(stylesheet: &mut Stylesheet, rules: Vec<Rule>, origin: Origin, media: &[MediaQueryList]) {
+    for rule in rules {
+	match rule {
+	    Rule::Qualified(prelude, block) => {
+		// rules with selectors that aren't valid (or supported) are dropped
+		if let Some(selectors) = selector::parse_selector_list(&prelude) {
+		    stylesheet.rules.push(StyleRule{selectors, declarations: parse_declarations(&block), origin, media: media.to_vec()});
+		}
+	    },
+	    Rule::At(name, prelude, Some(block)) if name.eq_ignore_ascii_case("media") => {
+		let mut media = media.to_vec();
+		media.push(media::parse_media_query_list(&prelude));
+		add_rules(stylesheet, parse_rules(&block, false), origin, &media);
+	    },
+	    Rule::At(name, _, Some(block)) if name.eq_ignore_ascii_case("font-face") => {
+		stylesheet.font_faces.extend(font::parse_font_face(&consume_declarations(&block)));
+	    },
+	    // other at-rules aren't supported yet (and @import in the wrong place is ignored)
+	    Rule::At(..) => {},
+	}
+    }
+}
+// parses what comes after @import, which is a url and then media queries
+fn parse_import(prelude: &[ComponentValue]) -> Option<Import> {
+    let mut values = prelude.iter().skip_while(|v| v == &&ComponentValue::Token(Token::Whitespace));
+    let url = match values.next()? {
+	ComponentValue::Token(Token::String(url)) => url.to_string(),
+	value => parse_url(value)?,
+    };
+    Some(Import{url, media: media::parse_media_query_list(&values.cloned().collect::<Vec<ComponentValue>>())})
+}
+
+// gets the url out of url(...) (which is only a token when it's not quoted)
+pub fn parse_url(value: &ComponentValue) -> Option<String> {
+    match value {
+	ComponentValue::Token(Token::Url(url)) => Some(url.to_string()),
+	ComponentValue::Function(name, arguments) if name.eq_ignore_ascii_case("url") => {
+	    match arguments.iter().filter(|v| v != &&ComponentValue::Token(Token::Whitespace)).collect::<Vec<_>>().as_slice() {
+		[ComponentValue::Token(Token::String(url))] => Some(url.to_string()),
+		_ => None,
+	    }
+	},
+	_ => None,
+    }
+}
+
+// parses a list of declarations, like in a style attribute
+pub fn parse_declaration_list(css: &str) -> Vec<Declaration> {
+    parse_declarations(&component_values(&tokenizer::tokenize(css)))
+}
+
+// writes component values back out as css
+pub fn serialize(values: &[ComponentValue]) -> String {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+// groups tokens into blocks and functions
+pub fn component_values(tokens: &[Token]) -> Vec<ComponentValue> {
+    let mut position = 0;
+    consume_component_values(tokens, &mut position, None)
+}
+// reads component values until a closing token (or the end)
+fn consume_component_values(tokens: &[Token], position: &mut usize, close: Option<&Token>) -> Vec<ComponentValue> {
+    let mut values = Vec::new();
+    while let Some(token) = tokens.get(*position) {
+	*position += 1;
+	if Some(token) == close {
+	    break;
+	}
+	values.push(match token {
+	    Token::OpenCurly => ComponentValue::Block('{', consume_component_values(tokens, position, Some(&Token::CloseCurly))),
+	    Token::OpenSquare => ComponentValue::Block('[', consume_component_values(tokens, position, Some(&Token::CloseSquare))),
+	    Token::OpenParen => ComponentValue::Block('(', consume_component_values(tokens, position, Some(&Token::CloseParen))),
+	    Token::Function(name) => ComponentValue::Function(name.to_string(), consume_component_values(tokens, position, Some(&Token::CloseParen))),
+	    _ => ComponentValue::Token(token.clone()),
+	});
+    }
+    values
+}
+
+// parses the rules of a stylesheet
+pub fn parse_stylesheet(css: &str) -> Vec<Rule> {
+    parse_rules(&component_values(&tokenizer::tokenize(css)), true)
+}
+
+// parses rules (top level is whether these are the rules of a stylesheet instead of inside of @media, etc.)
+pub fn parse_rules(values: &[ComponentValue], top_level: bool) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    let mut values = values.iter();
+    while let Some(value) = values.next() {
+	match value {
+	    ComponentValue::Token(Token::Whitespace) => {},
+	    // <!-- and --> are left over from hiding css from old browsers
+	    ComponentValue::Token(Token::Cdo | Token::Cdc) if top_level => {},
+	    ComponentValue::Token(Token::AtKeyword(name)) => {
+		let mut prelude = Vec::new();
+		let mut block = None;
+		for value in values.by_ref() {
+		    match value {
+			ComponentValue::Token(Token::Semicolon) => break,
+			ComponentValue::Block('{', contents) => {
+			    block = Some(contents.clone());
+			    break;
+			},
+			_ => prelude.push(value.clone()),
+		    }
+		}
+		rules.push(Rule::At(name.to_string(), prelude, block));
+	    },
+	    _ => {
+		// everything up to the {} is the selector
+		let mut prelude = Vec::new();
+		let mut next = Some(value);
+		while let Some(value) = next {
+		    if let ComponentValue::Block('{', contents) = value {
+			rules.push(Rule::Qualified(prelude, contents.clone()));
+			break;
+		    }
+		    prelude.push(value.clone());
+		    next = values.next();
+		}
+		// a rule without a block is dropped
+	    },
+	}
+    }
+    rules
+}
+
+// parses declarations, skipping ones that are broken
+// (values that don't work for their property are dropped, so they don't override ones that do,
+// and shorthands are replaced by their longhands)
+pub fn parse_declarations(values: &[ComponentValue]) -> Vec<Declaration> {
+    consume_declarations(values).into_iter()
+	.filter(|declaration| style::is_valid(&declaration.name, &declaration.value))
+	.flat_map(shorthand::expand)
+	.collect()
+}
+// parses declarations as they are written, like for the descriptors of @font-face
+fn consume_declarations(values: &[ComponentValue]) -> Vec<Declaration> {
+    let mut declarations = Vec::new();
+    let mut values = values.iter();
+    while let Some(value) = values.next() {
+	match value {
+	    ComponentValue::Token(Token::Whitespace | Token::Semicolon) => {},
+	    ComponentValue::Token(Token::AtKeyword(_)) => {
+		// at-rules in declarations aren't supported, so they are skipped
+		for value in values.by_ref() {
+		    if matches!(value, ComponentValue::Token(Token::Semicolon) | ComponentValue::Block('{', _)) {
+			break;
+		    }
+		}
+	    },
+	    ComponentValue::Token(Token::Ident(name)) => {
+		let mut declaration = Vec::new();
+		for value in values.by_ref() {
+		    if value == &ComponentValue::Token(Token::Semicolon) {
+			break;
+		    }
+		    declaration.push(value.clone());
+		}
+		if let Some(declaration) = parse_declaration(name, declaration) {
+		    declarations.push(declaration);
+		}
+	    },
+	    // anything else is an error, so everything up to the next ; is dropped
+	    _ => {
+		for value in values.by_ref() {
+		    if value == &ComponentValue::Token(Token::Semicolon) {
+			break;
+		    }
+		}
+	    },
+	}
+    }
+    declarations
+}
+// parses what comes after the name of a declaration
+fn parse_declaration(name: &str, values: Vec<ComponentValue>) -> Option<Declaration> {
+    let mut values = values.into_iter().skip_while(|v| v == &ComponentValue::Token(Token::Whitespace));
+    if values.next() != Some(ComponentValue::Token(Token::Colon)) {
+	return None;
+    }
+    let mut value = values.collect::<Vec<ComponentValue>>();
+    trim_whitespace(&mut value);
+    // !important at the end
+    let mut important = false;
+    if let Some(ComponentValue::Token(Token::Ident(ident))) = value.last() {
+	if ident.eq_ignore_ascii_case("important") {
+	    let bang = value[..value.len()-1].iter().rposition(|v| v != &ComponentValue::Token(Token::Whitespace));
+	    if let Some(bang) = bang {
+		if value[bang] == ComponentValue::Token(Token::Delim('!')) {
+		    important = true;
+		    value.truncate(bang);
+		    trim_whitespace(&mut value);
+		}
+	    }
+	}
+    }
+    // custom properties are case sensitive, and they are the only properties that can be empty
+    let name = if name.starts_with("--") {
+	name.to_string()
+    } else if value.is_empty() {
+	return None;
+    } else {
+	name.to_ascii_lowercase()
+    };
+    Some(Declaration{name, value, important, shorthand: None})
+}
+// removes whitespace at the start and end
+fn trim_whitespace(values: &mut Vec<ComponentValue>) {
+    while values.last() == Some(&ComponentValue::Token(Token::Whitespace)) {
+	values.pop();
+    }
+    while values.first() == Some(&ComponentValue::Token(Token::Whitespace)) {
+	values.remove(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // names and values of declarations, written back out
+    fn declarations(css: &str) -> Vec<(String, String, bool)> {
+	parse_declaration_list(css).into_iter().map(|d| (d.name, serialize(&d.value), d.important)).collect()
+    }
+
+    #[test]
+    fn last_declaration_doesnt_need_a_semicolon() {
+	assert_eq!(declarations("color: red; display:block"), [("color".to_string(), "red".to_string(), false),
+								("display".to_string(), "block".to_string(), false)]);
+    }
+
+    #[test]
+    fn important() {
+	assert_eq!(declarations("color: red ! IMPORTANT"), [("color".to_string(), "red".to_string(), true)]);
+    }
+
+    #[test]
+    fn broken_declarations_are_skipped() {
+	assert_eq!(declarations("color red; 12: x; --a: ; display: block"), [("--a".to_string(), String::new(), false),
+										("display".to_string(), "block".to_string(), false)]);
+	// a ; inside of a block or string doesn't end the declaration
+	assert_eq!(declarations("--a: {x;y}; --b: 'c;d'").len(), 2);
+    }
+
+    #[test]
+    fn rules_with_pseudo_classes_and_strings() {
+	let stylesheet = parse("a:hover { content: '}' } p{color:blue}".to_string(), Origin::Author);
+	assert_eq!(stylesheet.rules.len(), 2);
+	assert_eq!(stylesheet.rules[1].declarations[0].name, "color");
+    }
+
+    #[test]
+    fn rules_inside_of_media() {
+	let stylesheet = parse("@media print { @media (min-width: 10px) { p {} } a {} } b {}".to_string(), Origin::Author);
+	let media: Vec<usize> = stylesheet.rules.iter().map(|rule| rule.media.len()).collect();
+	assert_eq!(media, [2, 1, 0]);
+    }
+
+    #[test]
+    fn broken_rules_are_dropped() {
+	// the invalid selector only drops its own rule, and a rule without a block is dropped at the end
+	let stylesheet = parse("p..x { color: red } @unknown { x } div { color: red } span".to_string(), Origin::Author);
+	assert_eq!(stylesheet.rules.len(), 1);
+	assert_eq!(stylesheet.rules[0].selectors.len(), 1);
+    }
+
+    #[test]
+    fn imports_only_at_the_start() {
+	let stylesheet = parse("@charset 'utf-8'; @import 'a.css' print; @import url(b.css); p {} @import 'c.css';".to_string(), Origin::Author);
+	let urls: Vec<&str> = stylesheet.imports.iter().map(|import| import.url.as_str()).collect();
+	assert_eq!(urls, ["a.css", "b.css"]);
+	assert!(!stylesheet.imports[0].media.0.is_empty() && stylesheet.imports[1].media.0.is_empty());
+    }
+}
@@ -0,0 +1,481 @@
+use super::ComponentValue;
+use super::tokenizer::{self, Token};
+
+// selectors that check one thing about an element
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimpleSelector {
+    // *
+    Universal,
+    // tag name
+    Type(String),
+    Class(String),
+    Id(String),
+    // [name], or [name=value] with how the value is compared and whether case is ignored
+    Attribute(String, Option<(AttributeOperator, String, bool)>),
+    PseudoClass(PseudoClass),
+}
+
+// pseudo-classes, which check where an element is or what is happening to it
+#[derive(Debug, Clone, PartialEq)]
+pub enum PseudoClass {
+    // :nth-child(an+b), and whether it counts from the end (:first-child is :nth-child(1))
+    NthChild(i32, i32, bool),
+    // :nth-of-type(an+b), only counting elements with the same tag name
+    NthOfType(i32, i32, bool),
+    OnlyChild,
+    OnlyOfType,
+    // :not(), :is() and :where() (which only differ from :is() in specificity)
+    Not(Vec<Selector>),
+    Is(Vec<Selector>),
+    Where(Vec<Selector>),
+    // :has(), with selectors that start at :scope
+    Has(Vec<Selector>),
+    Root,
+    Empty,
+    // the element that relative selectors start from
+    Scope,
+    Hover,
+    Active,
+    Focus,
+    Link,
+    Visited,
+}
+
+// how the value in an attribute selector is compared
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AttributeOperator {
+    // [a=b]
+    Equals,
+    // [a~=b], one of the words is b
+    Includes,
+    // [a|=b], b or starts with b-
+    DashMatch,
+    // [a^=b]
+    Prefix,
+    // [a$=b]
+    Suffix,
+    // [a*=b]
+    Substring,
+}
+
+// how compound selectors are joined
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Combinator {
+    // a b
+    Descendant,
+    // a > b
+    Child,
+    // a + b
+    NextSibling,
+    // a ~ b
+    SubsequentSibling,
+}
+
+// compound selectors (like div.note) joined by combinators
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    // what has to be true of the element itself (the rightmost compound selector)
+    pub compound: Vec<SimpleSelector>,
+    // what has to be true of the elements around it, like "div >" in "div > p"
+    pub context: Option<(Combinator, Box<Selector>)>,
+}
+
+// how specific a selector is: ids, then classes, attributes and pseudo-classes, then tag names
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Specificity(pub u32, pub u32, pub u32);
+impl std::ops::Add for Specificity {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+	Specificity(self.0+other.0, self.1+other.1, self.2+other.2)
+    }
+}
+
+impl Selector {
+    // adds up the specificity of every compound selector
+    pub fn specificity(&self) -> Specificity {
+	let compound = self.compound.iter().map(|simple| simple.specificity()).fold(Specificity::default(), |a, b| a+b);
+	match &self.context {
+	    Some((_, context)) => compound + context.specificity(),
+	    None => compound,
+	}
+    }
+}
+impl SimpleSelector {
+    pub fn specificity(&self) -> Specificity {
+	match self {
+	    SimpleSelector::Universal => Specificity(0, 0, 0),
+	    SimpleSelector::Type(_) => Specificity(0, 0, 1),
+	    SimpleSelector::Id(_) => Specificity(1, 0, 0),
+	    SimpleSelector::Class(_) | SimpleSelector::Attribute(..) => Specificity(0, 1, 0),
+	    // :is(), :not() and :has() are as specific as the most specific selector in them, and :where() is never specific
+	    SimpleSelector::PseudoClass(PseudoClass::Is(selectors) | PseudoClass::Not(selectors)) => {
+		selectors.iter().map(|s| s.specificity()).max().unwrap_or_default()
+	    },
+	    SimpleSelector::PseudoClass(PseudoClass::Has(selectors)) => {
+		// the :scope that every selector in :has() starts from isn't written out, so it doesn't count
+		let most = selectors.iter().map(|s| s.specificity()).max().unwrap_or_default();
+		Specificity(most.0, most.1.saturating_sub(1), most.2)
+	    },
+	    SimpleSelector::PseudoClass(PseudoClass::Where(_)) => Specificity(0, 0, 0),
+	    SimpleSelector::PseudoClass(_) => Specificity(0, 1, 0),
+	}
+    }
+}
+
+// parses a comma separated list of selectors (None if any of them are invalid)
+pub fn parse(selectors: &str) -> Option<Vec<Selector>> {
+    parse_selector_list(&super::component_values(&tokenizer::tokenize(selectors)))
+}
+
+pub fn parse_selector_list(values: &[ComponentValue]) -> Option<Vec<Selector>> {
+    values.split(|v| v == &ComponentValue::Token(Token::Comma)).map(parse_selector).collect()
+}
+
+fn parse_selector(values: &[ComponentValue]) -> Option<Selector> {
+    let mut compounds = Vec::new();
+    let mut combinators = Vec::new();
+    let mut compound = Vec::new();
+    // combinator between the last compound selector and the next one
+    let mut combinator = None;
+    let mut index = 0;
+    while let Some(value) = values.get(index) {
+	index += 1;
+	match value {
+	    ComponentValue::Token(Token::Whitespace) => {
+		if !compound.is_empty() {
+		    compounds.push(std::mem::take(&mut compound));
+		    combinator = Some(Combinator::Descendant);
+		}
+	    },
+	    ComponentValue::Token(Token::Delim(c @ ('>' | '+' | '~'))) => {
+		if !compound.is_empty() {
+		    compounds.push(std::mem::take(&mut compound));
+		} else if compounds.is_empty() || combinator != Some(Combinator::Descendant) {
+		    // combinators need something before them, and can't be next to each other
+		    return None;
+		}
+		combinator = Some(match c {
+		    '>' => Combinator::Child,
+		    '+' => Combinator::NextSibling,
+		    _ => Combinator::SubsequentSibling,
+		});
+	    },
+	    _ => {
+		if let Some(combinator) = combinator.take() {
+		    combinators.push(combinator);
+		}
+		let simple = match value {
+		    ComponentValue::Token(Token::Delim('*')) => SimpleSelector::Universal,
+		    ComponentValue::Token(Token::Ident(name)) => SimpleSelector::Type(name.to_string()),
+		    ComponentValue::Token(Token::Hash(name, true)) => SimpleSelector::Id(name.to_string()),
+		    ComponentValue::Token(Token::Delim('.')) => match values.get(index) {
+			Some(ComponentValue::Token(Token::Ident(name))) => {
+			    index += 1;
+			    SimpleSelector::Class(name.to_string())
+			},
+			_ => return None,
+		    },
+		    ComponentValue::Block('[', contents) => parse_attribute_selector(contents)?,
+		    ComponentValue::Token(Token::Colon) => {
+			let pseudo_class = match values.get(index) {
+			    Some(ComponentValue::Token(Token::Ident(name))) => parse_pseudo_class(name)?,
+			    Some(ComponentValue::Function(name, arguments)) => parse_functional_pseudo_class(name, arguments)?,
+			    // pseudo-elements (::before) aren't supported
+			    _ => return None,
+			};
+			index += 1;
+			SimpleSelector::PseudoClass(pseudo_class)
+		    },
+		    _ => return None,
+		};
+		// tag names and * have to come first
+		if matches!(simple, SimpleSelector::Universal | SimpleSelector::Type(_)) && !compound.is_empty() {
+		    return None;
+		}
+		compound.push(simple);
+	    },
+	}
+    }
+    if !compound.is_empty() {
+	compounds.push(compound);
+    } else if combinator.is_some_and(|c| c != Combinator::Descendant) {
+	// combinators need something after them
+	return None;
+    }
+    // the compound selectors are nested so that matching can start from the right
+    let mut compounds = compounds.into_iter();
+    let mut selector = Selector{compound: compounds.next()?, context: None};
+    for (combinator, compound) in combinators.into_iter().zip(compounds) {
+	selector = Selector{compound, context: Some((combinator, Box::new(selector)))};
+    }
+    Some(selector)
+}
+
+// parses what's inside of the [] of an attribute selector
+fn parse_attribute_selector(values: &[ComponentValue]) -> Option<SimpleSelector> {
+    let mut values = values.iter().filter(|v| v != &&ComponentValue::Token(Token::Whitespace));
+    let name = match values.next() {
+	Some(ComponentValue::Token(Token::Ident(name))) => name.to_string(),
+	_ => return None,
+    };
+    let operator = match values.next() {
+	None => return Some(SimpleSelector::Attribute(name, None)),
+	Some(ComponentValue::Token(Token::Delim('='))) => AttributeOperator::Equals,
+	Some(ComponentValue::Token(Token::Delim(c))) => {
+	    let operator = match c {
+		'~' => AttributeOperator::Includes,
+		'|' => AttributeOperator::DashMatch,
+		'^' => AttributeOperator::Prefix,
+		'$' => AttributeOperator::Suffix,
+		'*' => AttributeOperator::Substring,
+		_ => return None,
+	    };
+	    if values.next() != Some(&ComponentValue::Token(Token::Delim('='))) {
+		return None;
+	    }
+	    operator
+	},
+	_ => return None,
+    };
+    let value = match values.next() {
+	Some(ComponentValue::Token(Token::Ident(value) | Token::String(value))) => value.to_string(),
+	_ => return None,
+    };
+    // [a=b i] ignores case, [a=b s] doesn't
+    let ignore_case = match values.next() {
+	None => false,
+	Some(ComponentValue::Token(Token::Ident(flag))) if flag.eq_ignore_ascii_case("i") => true,
+	Some(ComponentValue::Token(Token::Ident(flag))) if flag.eq_ignore_ascii_case("s") => false,
+	_ => return None,
+    };
+    if values.next().is_some() {
+	return None;
+    }
+    Some(SimpleSelector::Attribute(name, Some((operator, value, ignore_case))))
+}
+
+// parses pseudo-classes without arguments, like :hover
+fn parse_pseudo_class(name: &str) -> Option<PseudoClass> {
+    Some(match name.to_ascii_lowercase().as_str() {
+	"first-child" => PseudoClass::NthChild(0, 1, false),
+	"last-child" => PseudoClass::NthChild(0, 1, true),
+	"only-child" => PseudoClass::OnlyChild,
+	"first-of-type" => PseudoClass::NthOfType(0, 1, false),
+	"last-of-type" => PseudoClass::NthOfType(0, 1, true),
+	"only-of-type" => PseudoClass::OnlyOfType,
+	"root" => PseudoClass::Root,
+	"empty" => PseudoClass::Empty,
+	"scope" => PseudoClass::Scope,
+	"hover" => PseudoClass::Hover,
+	"active" => PseudoClass::Active,
+	"focus" => PseudoClass::Focus,
+	"link" => PseudoClass::Link,
+	"visited" => PseudoClass::Visited,
+	_ => return None,
+    })
+}
+
+// parses pseudo-classes that take arguments, like :nth-child(2n+1)
+fn parse_functional_pseudo_class(name: &str, arguments: &[ComponentValue]) -> Option<PseudoClass> {
+    Some(match name.to_ascii_lowercase().as_str() {
+	"nth-child" => {
+	    let (a, b) = parse_nth(arguments)?;
+	    PseudoClass::NthChild(a, b, false)
+	},
+	"nth-last-child" => {
+	    let (a, b) = parse_nth(arguments)?;
+	    PseudoClass::NthChild(a, b, true)
+	},
+	"nth-of-type" => {
+	    let (a, b) = parse_nth(arguments)?;
+	    PseudoClass::NthOfType(a, b, false)
+	},
+	"nth-last-of-type" => {
+	    let (a, b) = parse_nth(arguments)?;
+	    PseudoClass::NthOfType(a, b, true)
+	},
+	"not" => PseudoClass::Not(parse_selector_list(arguments)?),
+	// selectors in :is() and :where() that aren't valid are ignored instead of breaking everything
+	"is" => PseudoClass::Is(parse_forgiving_selector_list(arguments)),
+	"where" => PseudoClass::Where(parse_forgiving_selector_list(arguments)),
+	"has" => PseudoClass::Has(arguments.split(|v| v == &ComponentValue::Token(Token::Comma)).map(parse_relative_selector).collect::<Option<_>>()?),
+	_ => return None,
+    })
+}
+
+fn parse_forgiving_selector_list(values: &[ComponentValue]) -> Vec<Selector> {
+    values.split(|v| v == &ComponentValue::Token(Token::Comma)).filter_map(parse_selector).collect()
+}
+
+// parses a selector that can start with a combinator, like "> img" in :has(> img)
+fn parse_relative_selector(values: &[ComponentValue]) -> Option<Selector> {
+    let start = values.iter().position(|v| v != &ComponentValue::Token(Token::Whitespace))?;
+    let (combinator, values) = match &values[start] {
+	ComponentValue::Token(Token::Delim('>')) => (Combinator::Child, &values[start+1..]),
+	ComponentValue::Token(Token::Delim('+')) => (Combinator::NextSibling, &values[start+1..]),
+	ComponentValue::Token(Token::Delim('~')) => (Combinator::SubsequentSibling, &values[start+1..]),
+	_ => (Combinator::Descendant, values),
+    };
+    let mut selector = parse_selector(values)?;
+    anchor_to_scope(&mut selector, combinator);
+    Some(selector)
+}
+// makes the leftmost compound selector relative to :scope
+fn anchor_to_scope(selector: &mut Selector, combinator: Combinator) {
+    match &mut selector.context {
+	Some((_, context)) => anchor_to_scope(context, combinator),
+	None => selector.context = Some((combinator, Box::new(Selector{compound: vec![SimpleSelector::PseudoClass(PseudoClass::Scope)], context: None}))),
+    }
+}
+
+// parses the an+b in :nth-child(), giving a and b
+fn parse_nth(values: &[ComponentValue]) -> Option<(i32, i32)> {
+    let mut tokens = Vec::new();
+    for value in values {
+	match value {
+	    ComponentValue::Token(Token::Whitespace) => {},
+	    ComponentValue::Token(token) => tokens.push(token),
+	    _ => return None,
+	}
+    }
+    // "2n-1" is a number with the unit "n-1", and "-n+3" is the identifier "-n" and the number 3
+    let (a, rest, tokens) = match tokens.as_slice() {
+	[Token::Ident(ident)] if ident.eq_ignore_ascii_case("odd") => return Some((2, 1)),
+	[Token::Ident(ident)] if ident.eq_ignore_ascii_case("even") => return Some((2, 0)),
+	[Token::Number(b, true)] => return Some((0, *b as i32)),
+	[Token::Dimension(a, true, unit), tokens @ ..] => (*a as i32, unit.to_ascii_lowercase(), tokens),
+	[Token::Delim('+'), Token::Ident(ident), tokens @ ..] => (1, ident.to_ascii_lowercase(), tokens),
+	[Token::Ident(ident), tokens @ ..] => match ident.strip_prefix('-') {
+	    Some(ident) => (-1, ident.to_ascii_lowercase(), tokens),
+	    None => (1, ident.to_ascii_lowercase(), tokens),
+	},
+	_ => return None,
+    };
+    let b = match (rest.as_str(), tokens) {
+	("n", []) => 0,
+	// the sign of the number is lost when it's tokenized, so +1 and 1 look the same
+	("n", [Token::Number(b, true)]) => *b as i32,
+	("n", [Token::Delim('+'), Token::Number(b, true)]) if *b >= 0. => *b as i32,
+	("n", [Token::Delim('-'), Token::Number(b, true)]) if *b >= 0. => -*b as i32,
+	("n-", [Token::Number(b, true)]) if *b >= 0. => -*b as i32,
+	(rest, []) => -rest.strip_prefix("n-")?.parse::<i32>().ok()?,
+	_ => return None,
+    };
+    Some((a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_selector(name: &str) -> SimpleSelector {
+	SimpleSelector::Type(name.to_string())
+    }
+
+    #[test]
+    fn compound_selectors() {
+	let selectors = parse("div.note#main").unwrap();
+	assert_eq!(selectors, [Selector{compound: vec![type_selector("div"), SimpleSelector::Class("note".to_string()), SimpleSelector::Id("main".to_string())], context: None}]);
+	// tag names have to come first
+	assert!(parse(".note*").is_none());
+    }
+
+    #[test]
+    fn combinators_are_nested_from_the_right() {
+	let selector = parse("ul > li  +p~ a b").unwrap().remove(0);
+	let mut combinators = Vec::new();
+	let mut compounds = vec![selector.compound.clone()];
+	let mut context = selector.context;
+	while let Some((combinator, selector)) = context {
+	    combinators.push(combinator);
+	    compounds.push(selector.compound.clone());
+	    context = selector.context;
+	}
+	assert_eq!(combinators, [Combinator::Descendant, Combinator::SubsequentSibling, Combinator::NextSibling, Combinator::Child]);
+	assert_eq!(compounds, [vec![type_selector("b")], vec![type_selector("a")], vec![type_selector("p")], vec![type_selector("li")], vec![type_selector("ul")]]);
+    }
+
+    #[test]
+    fn selector_lists() {
+	assert_eq!(parse("a, b ,c").unwrap().len(), 3);
+	// one invalid selector makes the whole list invalid
+	assert!(parse("a, b..c").is_none());
+	assert!(parse("a,,b").is_none());
+	assert!(parse("").is_none());
+    }
+
+    #[test]
+    fn combinators_need_selectors_on_both_sides() {
+	assert!(parse("> a").is_none());
+	assert!(parse("a >").is_none());
+	assert!(parse("a > + b").is_none());
+	assert!(parse("a ").is_some());
+    }
+
+    #[test]
+    fn attribute_selectors() {
+	let attribute = |css: &str| match parse(css).unwrap().remove(0).compound.remove(0) {
+	    SimpleSelector::Attribute(name, value) => (name, value),
+	    simple => panic!("expected an attribute selector, got {:?}", simple),
+	};
+	assert_eq!(attribute("[href]"), ("href".to_string(), None));
+	assert_eq!(attribute("[ lang |= en ]"), ("lang".to_string(), Some((AttributeOperator::DashMatch, "en".to_string(), false))));
+	assert_eq!(attribute("[type='a b' i]"), ("type".to_string(), Some((AttributeOperator::Equals, "a b".to_string(), true))));
+	assert_eq!(attribute("[a^=b s]").1.unwrap().0, AttributeOperator::Prefix);
+	assert_eq!(attribute("[a$=b]").1.unwrap().0, AttributeOperator::Suffix);
+	assert_eq!(attribute("[a*=b]").1.unwrap().0, AttributeOperator::Substring);
+	assert_eq!(attribute("[a~=b]").1.unwrap().0, AttributeOperator::Includes);
+	assert!(parse("[a=]").is_none());
+	assert!(parse("[a=b x]").is_none());
+	assert!(parse("[a==b]").is_none());
+	assert!(parse("[=b]").is_none());
+    }
+
+    #[test]
+    fn nth() {
+	let nth = |css: &str| match parse(&format!(":nth-child({})", css)).map(|mut s| s.remove(0).compound.remove(0)) {
+	    Some(SimpleSelector::PseudoClass(PseudoClass::NthChild(a, b, false))) => Some((a, b)),
+	    _ => None,
+	};
+	assert_eq!(nth("odd"), Some((2, 1)));
+	assert_eq!(nth("EVEN"), Some((2, 0)));
+	assert_eq!(nth("3"), Some((0, 3)));
+	assert_eq!(nth("2n+1"), Some((2, 1)));
+	assert_eq!(nth("2n-1"), Some((2, -1)));
+	assert_eq!(nth(" 2n - 1 "), Some((2, -1)));
+	assert_eq!(nth("-n+3"), Some((-1, 3)));
+	assert_eq!(nth("+n"), Some((1, 0)));
+	assert_eq!(nth("n- 2"), Some((1, -2)));
+	assert_eq!(nth("2n+"), None);
+	assert_eq!(nth("2.5n"), None);
+	assert_eq!(nth("2n + -1"), None);
+    }
+
+    #[test]
+    fn pseudo_classes() {
+	assert_eq!(parse(":first-child").unwrap()[0].compound, [SimpleSelector::PseudoClass(PseudoClass::NthChild(0, 1, false))]);
+	assert_eq!(parse(":LAST-of-type").unwrap()[0].compound, [SimpleSelector::PseudoClass(PseudoClass::NthOfType(0, 1, true))]);
+	// invalid selectors in :is() and :where() are skipped, but not in :not()
+	assert_eq!(parse(":is(a, b..c)").unwrap()[0].compound, [SimpleSelector::PseudoClass(PseudoClass::Is(parse("a").unwrap()))]);
+	assert!(parse(":not(a, b..c)").is_none());
+	assert!(parse(":has(> img, + p)").is_some());
+	assert!(parse(":unknown").is_none());
+	assert!(parse("p::before").is_none());
+    }
+
+    #[test]
+    fn specificity() {
+	let specificity = |css: &str| parse(css).unwrap()[0].specificity();
+	assert_eq!(specificity("*"), Specificity(0, 0, 0));
+	assert_eq!(specificity("ul li > a"), Specificity(0, 0, 3));
+	assert_eq!(specificity("a.b[c]:hover"), Specificity(0, 3, 1));
+	assert_eq!(specificity("#a #b .c"), Specificity(2, 1, 0));
+	// the most specific selector inside of :is(), :not() and :has() counts, and :where() never does
+	assert_eq!(specificity(":is(p, #a .b)"), Specificity(1, 1, 0));
+	assert_eq!(specificity(":not(p, .b)"), Specificity(0, 1, 0));
+	assert_eq!(specificity("div:has(> img)"), Specificity(0, 0, 2));
+	assert_eq!(specificity(":where(#a) p"), Specificity(0, 0, 1));
+	assert!(Specificity(1, 0, 0) > Specificity(0, 20, 20));
+    }
+}
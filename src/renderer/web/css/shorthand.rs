@@ -0,0 +1,394 @@
+use super::{component_values, serialize, tokenizer::{self, Token}, ComponentValue, Declaration};
+use crate::renderer::web::{font, style};
+
+// shorthand properties, and the longhands they set
+const SHORTHANDS: [(&str, &[&str]);13] = [
+    ("margin", &["margin-top", "margin-right", "margin-bottom", "margin-left"]),
+    ("padding", &["padding-top", "padding-right", "padding-bottom", "padding-left"]),
+    ("border-width", &["border-top-width", "border-right-width", "border-bottom-width", "border-left-width"]),
+    ("border-style", &["border-top-style", "border-right-style", "border-bottom-style", "border-left-style"]),
+    ("border-color", &["border-top-color", "border-right-color", "border-bottom-color", "border-left-color"]),
+    ("border-top", &["border-top-width", "border-top-style", "border-top-color"]),
+    ("border-right", &["border-right-width", "border-right-style", "border-right-color"]),
+    ("border-bottom", &["border-bottom-width", "border-bottom-style", "border-bottom-color"]),
+    ("border-left", &["border-left-width", "border-left-style", "border-left-color"]),
+    ("border", &["border-top-width", "border-top-style", "border-top-color",
+		 "border-right-width", "border-right-style", "border-right-color",
+		 "border-bottom-width", "border-bottom-style", "border-bottom-color",
+		 "border-left-width", "border-left-style", "border-left-color"]),
+    ("font", &["font-style", "font-variant", "font-weight", "font-stretch", "font-size", "line-height", "font-family"]),
+    ("background", &["background-image", "background-position", "background-size", "background-repeat",
+		     "background-attachment", "background-origin", "background-clip", "background-color"]),
+    ("list-style", &["list-style-position", "list-style-image", "list-style-type"]),
+];
+
+// the longhands a shorthand sets, or None if it isn't a shorthand
+pub fn longhands(name: &str) -> Option<&'static [&'static str]> {
+    SHORTHANDS.iter().find(|(shorthand, _)| *shorthand == name).map(|(_, longhands)| *longhands)
+}
+
+// turns a declaration of a shorthand into declarations of its longhands, or nothing if its value doesn't work
+// (other declarations are left alone)
+pub fn expand(declaration: Declaration) -> Vec<Declaration> {
+    let longhands = match longhands(&declaration.name) {
+	Some(longhands) => longhands,
+	None => return vec![declaration],
+    };
+    let keyword = matches!(without_whitespace(&declaration.value).as_slice(), [keyword] if is_css_wide_keyword(keyword));
+    if keyword || style::has_var(&declaration.value) {
+	// keywords like inherit go to every longhand, and var()s have to be substituted before the value can be split up
+	let shorthand = if keyword { None } else { Some(declaration.name.clone()) };
+	return longhands.iter().map(|longhand| Declaration{name: longhand.to_string(),
+							  value: declaration.value.clone(),
+							  important: declaration.important,
+							  shorthand: shorthand.clone()}).collect();
+    }
+    match split(&declaration.name, &without_whitespace(&declaration.value)) {
+	Some(values) => values.into_iter().map(|(longhand, value)| Declaration{name: longhand.to_string(),
+									      value,
+									      important: declaration.important,
+									      shorthand: None}).collect(),
+	None => Vec::new(),
+    }
+}
+
+// works out the value of one longhand from the value of its shorthand, once its var()s are substituted
+pub fn longhand(shorthand: &str, value: &str, longhand: &str) -> Option<String> {
+    let values = without_whitespace(&component_values(&tokenizer::tokenize(value)));
+    split(shorthand, &values)?.into_iter().find(|(name, _)| *name == longhand).map(|(_, value)| serialize(&value))
+}
+
+// splits the value of a shorthand (without whitespace) into the values of its longhands
+fn split(shorthand: &str, values: &[ComponentValue]) -> Option<Vec<(&'static str, Vec<ComponentValue>)>> {
+    let longhands = longhands(shorthand)?;
+    // keywords like inherit can't be mixed with other values
+    if values.is_empty() || values.iter().any(is_css_wide_keyword) {
+	return None;
+    }
+    let values = match shorthand {
+	"margin" | "padding" | "border-width" | "border-style" | "border-color" => sides(values, longhands[0])?.to_vec(),
+	"border" => border_side(values)?.iter().cycle().take(12).cloned().collect(),
+	"border-top" | "border-right" | "border-bottom" | "border-left" => border_side(values)?.to_vec(),
+	"font" => font(values)?,
+	"background" => background(values)?,
+	"list-style" => list_style(values)?,
+	_ => return None,
+    };
+    Some(longhands.iter().copied().zip(values).collect())
+}
+
+// 1 to 4 values for the top, right, bottom and left sides of a box
+fn sides(values: &[ComponentValue], longhand: &str) -> Option<[Vec<ComponentValue>;4]> {
+    if !values.iter().all(|value| is_valid(longhand, value)) {
+	return None;
+    }
+    let [top, right, bottom, left] = match values {
+	[all] => [all, all, all, all],
+	[vertical, horizontal] => [vertical, horizontal, vertical, horizontal],
+	[top, horizontal, bottom] => [top, horizontal, bottom, horizontal],
+	[top, right, bottom, left] => [top, right, bottom, left],
+	_ => return None,
+    };
+    Some([vec![top.clone()], vec![right.clone()], vec![bottom.clone()], vec![left.clone()]])
+}
+
+// a width, style and color in any order, like 1px solid red
+fn border_side(values: &[ComponentValue]) -> Option<[Vec<ComponentValue>;3]> {
+    let mut parts = [None, None, None];
+    for value in values {
+	let part = ["border-top-width", "border-top-style", "border-top-color"].iter().zip(parts.iter())
+	    .position(|(longhand, part)| part.is_none() && is_valid(longhand, value))?;
+	parts[part] = Some(vec![value.clone()]);
+    }
+    Some(parts.map(|part| part.unwrap_or_else(initial)))
+}
+
+// [style || variant || weight || stretch]? size [/ line-height]? family, like italic bold 16px/1.4 serif
+fn font(values: &[ComponentValue]) -> Option<Vec<Vec<ComponentValue>>> {
+    // fonts of parts of the browser, which are just the default font here
+    if let [ComponentValue::Token(Token::Ident(system_font))] = values {
+	if ["caption", "icon", "menu", "message-box", "small-caption", "status-bar"].contains(&system_font.to_ascii_lowercase().as_str()) {
+	    return Some(vec![initial(); 7]);
+	}
+    }
+    // style, variant, weight and stretch can come before the size in any order (and normal can be any of them)
+    let mut parts = [None, None, None, None];
+    let mut index = 0;
+    while let Some(value) = values.get(index).filter(|_| index < 4) {
+	if is_ident(value, &["normal"]) {
+	    index += 1;
+	    continue;
+	}
+	let checks: [&dyn Fn(&ComponentValue) -> bool;4] = [&|v| is_valid("font-style", v),
+							    &|v| is_ident(v, &["small-caps"]),
+							    &|v| is_valid("font-weight", v),
+							    &|v| is_ident(v, &FONT_STRETCHES)];
+	match checks.iter().zip(parts.iter()).position(|(check, part)| part.is_none() && check(value)) {
+	    Some(part) => parts[part] = Some(vec![value.clone()]),
+	    None => break,
+	}
+	index += 1;
+    }
+    let size = values.get(index).filter(|value| is_valid("font-size", value))?;
+    index += 1;
+    let mut line_height = None;
+    if values.get(index) == Some(&ComponentValue::Token(Token::Delim('/'))) {
+	let value = values.get(index+1).filter(|value| is_valid("line-height", value))?;
+	line_height = Some(vec![value.clone()]);
+	index += 2;
+    }
+    let family = &values[index..];
+    font::parse_family_names(family)?;
+    let [style, variant, weight, stretch] = parts.map(|part| part.unwrap_or_else(initial));
+    Some(vec![style, variant, weight, stretch, vec![size.clone()], line_height.unwrap_or_else(initial), with_whitespace(family)])
+}
+const FONT_STRETCHES: [&str;9] = ["ultra-condensed", "extra-condensed", "condensed", "semi-condensed", "semi-expanded",
+				  "expanded", "extra-expanded", "ultra-expanded", "normal"];
+
+// layers separated by commas, each with an image, position / size, repeat, attachment and boxes in any order
+// (and a color, but only in the last one)
+fn background(values: &[ComponentValue]) -> Option<Vec<Vec<ComponentValue>>> {
+    let layers = values.split(|v| v == &ComponentValue::Token(Token::Comma)).collect::<Vec<&[ComponentValue]>>();
+    let initials = ["none", "0% 0%", "auto", "repeat", "scroll", "padding-box", "border-box", "transparent"];
+    let mut longhands = vec![Vec::new(); 8];
+    for (index, layer) in layers.iter().enumerate() {
+	let last = index == layers.len()-1;
+	let parts = background_layer(layer, last)?;
+	for (part, (longhand, (value, initial))) in longhands.iter_mut().zip(parts.into_iter().zip(initials)).enumerate() {
+	    // the color is only in the last layer, and there's only one of it
+	    if part == 7 && !last {
+		continue;
+	    }
+	    if index > 0 && part != 7 {
+		longhand.push(ComponentValue::Token(Token::Comma));
+		longhand.push(ComponentValue::Token(Token::Whitespace));
+	    }
+	    longhand.append(&mut value.unwrap_or_else(|| component_values(&tokenizer::tokenize(initial))));
+	}
+    }
+    Some(longhands)
+}
+fn background_layer(values: &[ComponentValue], last: bool) -> Option<[Option<Vec<ComponentValue>>;8]> {
+    let [mut image, mut position, mut size, mut repeat, mut attachment, mut origin, mut clip, mut color] = [None, None, None, None, None, None, None, None];
+    let mut index = 0;
+    while let Some(value) = values.get(index) {
+	index += 1;
+	if image.is_none() && (is_ident(value, &["none"]) || is_image(value)) {
+	    image = Some(vec![value.clone()]);
+	} else if position.is_none() && is_position(value) {
+	    // up to four values for the position, then a / and one or two for the size
+	    let start = index-1;
+	    while index < values.len() && index-start < 4 && is_position(&values[index]) {
+		index += 1;
+	    }
+	    position = Some(with_whitespace(&values[start..index]));
+	    if values.get(index) == Some(&ComponentValue::Token(Token::Delim('/'))) {
+		let start = index+1;
+		index = start;
+		let is_size = |value: &ComponentValue| is_ident(value, &["auto"]) || is_valid("padding-top", value);
+		match values.get(index) {
+		    Some(value) if is_ident(value, &["cover", "contain"]) => index += 1,
+		    Some(value) if is_size(value) => {
+			index += 1;
+			if values.get(index).is_some_and(is_size) {
+			    index += 1;
+			}
+		    },
+		    _ => return None,
+		}
+		size = Some(with_whitespace(&values[start..index]));
+	    }
+	} else if repeat.is_none() && is_ident(value, &["repeat-x", "repeat-y"]) {
+	    repeat = Some(vec![value.clone()]);
+	} else if repeat.is_none() && is_ident(value, &REPEATS) {
+	    let start = index-1;
+	    if values.get(index).is_some_and(|value| is_ident(value, &REPEATS)) {
+		index += 1;
+	    }
+	    repeat = Some(with_whitespace(&values[start..index]));
+	} else if attachment.is_none() && is_ident(value, &["scroll", "fixed", "local"]) {
+	    attachment = Some(vec![value.clone()]);
+	} else if clip.is_none() && is_ident(value, &["border-box", "padding-box", "content-box"]) {
+	    // one box is both the origin and clip, and with two the first is the origin
+	    if origin.is_none() {
+		origin = Some(vec![value.clone()]);
+	    } else {
+		clip = Some(vec![value.clone()]);
+	    }
+	} else if last && color.is_none() && is_valid("background-color", value) {
+	    color = Some(vec![value.clone()]);
+	} else {
+	    return None;
+	}
+    }
+    if clip.is_none() {
+	clip = origin.clone();
+    }
+    Some([image, position, size, repeat, attachment, origin, clip, color])
+}
+const REPEATS: [&str;4] = ["repeat", "space", "round", "no-repeat"];
+
+// position, image and type in any order, where none is the image or type (or both) if they aren't set otherwise
+fn list_style(values: &[ComponentValue]) -> Option<Vec<Vec<ComponentValue>>> {
+    let (mut position, mut image, mut kind) = (None, None, None);
+    let mut nones = 0;
+    for value in values {
+	if is_ident(value, &["none"]) {
+	    nones += 1;
+	} else if position.is_none() && is_ident(value, &["inside", "outside"]) {
+	    position = Some(vec![value.clone()]);
+	} else if image.is_none() && is_image(value) {
+	    image = Some(vec![value.clone()]);
+	} else if kind.is_none() && matches!(value, ComponentValue::Token(Token::Ident(_) | Token::String(_))) {
+	    kind = Some(vec![value.clone()]);
+	} else {
+	    return None;
+	}
+    }
+    let none = || Some(component_values(&tokenizer::tokenize("none")));
+    match (nones, image.is_some(), kind.is_some()) {
+	(0, _, _) => {},
+	(1, false, false) | (2, false, false) => (image, kind) = (none(), none()),
+	(1, false, true) => image = none(),
+	(1, true, false) => kind = none(),
+	_ => return None,
+    }
+    Some([position, image, kind].into_iter().map(|part| part.unwrap_or_else(initial)).collect())
+}
+
+fn is_valid(longhand: &str, value: &ComponentValue) -> bool {
+    style::is_valid(longhand, std::slice::from_ref(value))
+}
+fn is_ident(value: &ComponentValue, idents: &[&str]) -> bool {
+    matches!(value, ComponentValue::Token(Token::Ident(ident)) if idents.iter().any(|i| ident.eq_ignore_ascii_case(i)))
+}
+fn is_css_wide_keyword(value: &ComponentValue) -> bool {
+    is_ident(value, &["inherit", "initial", "unset", "revert"])
+}
+// url(...) or a gradient
+fn is_image(value: &ComponentValue) -> bool {
+    match value {
+	ComponentValue::Function(name, _) => name.to_ascii_lowercase().ends_with("gradient") || super::parse_url(value).is_some(),
+	_ => super::parse_url(value).is_some(),
+    }
+}
+// a keyword or length in a background position
+fn is_position(value: &ComponentValue) -> bool {
+    is_ident(value, &["left", "center", "right", "top", "bottom"]) || is_valid("margin-top", value) && !is_ident(value, &["auto"])
+}
+// what longhands are set to when their part of a shorthand is left out
+fn initial() -> Vec<ComponentValue> {
+    vec![ComponentValue::Token(Token::Ident(String::from("initial")))]
+}
+fn without_whitespace(values: &[ComponentValue]) -> Vec<ComponentValue> {
+    values.iter().filter(|v| v != &&ComponentValue::Token(Token::Whitespace)).cloned().collect()
+}
+// puts whitespace back between values, like in "Times New Roman"
+fn with_whitespace(values: &[ComponentValue]) -> Vec<ComponentValue> {
+    let mut spaced = Vec::new();
+    for (index, value) in values.iter().enumerate() {
+	if index > 0 && value != &ComponentValue::Token(Token::Comma) {
+	    spaced.push(ComponentValue::Token(Token::Whitespace));
+	}
+	spaced.push(value.clone());
+    }
+    spaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::web::css::parse_declaration_list;
+
+    // the declarations some css turns into, like margin-top: 1px
+    fn expanded(css: &str) -> Vec<String> {
+	parse_declaration_list(css).into_iter().map(|d| format!("{}: {}", d.name, serialize(&d.value))).collect()
+    }
+
+    #[test]
+    fn one_to_four_sides() {
+	assert_eq!(expanded("margin: 1px"), ["margin-top: 1px", "margin-right: 1px", "margin-bottom: 1px", "margin-left: 1px"]);
+	assert_eq!(expanded("margin: 1px auto"), ["margin-top: 1px", "margin-right: auto", "margin-bottom: 1px", "margin-left: auto"]);
+	assert_eq!(expanded("padding: 1px 2px 3px"), ["padding-top: 1px", "padding-right: 2px", "padding-bottom: 3px", "padding-left: 2px"]);
+	assert_eq!(expanded("padding: 1px 2px 3px 4px"), ["padding-top: 1px", "padding-right: 2px", "padding-bottom: 3px", "padding-left: 4px"]);
+	assert_eq!(expanded("border-color: red blue"), ["border-top-color: red", "border-right-color: blue", "border-bottom-color: red", "border-left-color: blue"]);
+	// too many values, or values that don't work for the longhands, drop the whole declaration
+	assert!(expanded("margin: 1px 2px 3px 4px 5px").is_empty());
+	assert!(expanded("padding: auto").is_empty());
+	assert!(expanded("padding: 1px -5px").is_empty());
+	assert!(expanded("border-style: solid red").is_empty());
+    }
+
+    #[test]
+    fn borders() {
+	let border = expanded("border: 1px solid red");
+	assert_eq!(border.len(), 12);
+	assert_eq!(border[..3], ["border-top-width: 1px", "border-top-style: solid", "border-top-color: red"]);
+	assert_eq!(border[9..], ["border-left-width: 1px", "border-left-style: solid", "border-left-color: red"]);
+	// the parts can be in any order, and ones that are left out are set to their initial value
+	assert_eq!(expanded("border-top: red 2px"), ["border-top-width: 2px", "border-top-style: initial", "border-top-color: red"]);
+	assert!(expanded("border: solid solid").is_empty());
+	assert!(expanded("border: 1px solid red blue").is_empty());
+	assert!(expanded("border: -1px solid").is_empty());
+    }
+
+    #[test]
+    fn fonts() {
+	assert_eq!(expanded("font: italic bold 16px/1.5 \"Times New Roman\", serif"),
+		   ["font-style: italic", "font-variant: initial", "font-weight: bold", "font-stretch: initial", "font-size: 16px",
+		    "line-height: 1.5", "font-family: \"Times New Roman\", serif"]);
+	// normal can be any of the parts before the size
+	assert_eq!(expanded("font: normal small-caps normal condensed 12px Arial"),
+		   ["font-style: initial", "font-variant: small-caps", "font-weight: initial", "font-stretch: condensed", "font-size: 12px",
+		    "line-height: initial", "font-family: Arial"]);
+	assert_eq!(expanded("font: caption").len(), 7);
+	// the size and family have to be there
+	assert!(expanded("font: bold serif").is_empty());
+	assert!(expanded("font: 12px").is_empty());
+	assert!(expanded("font: 12px/ serif").is_empty());
+	// line heights are normal, or a number or length that isn't negative
+	let line_height = |css: &str| expanded(css).get(5).cloned();
+	assert_eq!(line_height("font: 12px/normal serif").as_deref(), Some("line-height: normal"));
+	assert_eq!(line_height("font: 12px/2em serif").as_deref(), Some("line-height: 2em"));
+	assert_eq!(line_height("font: 12px/calc(1 + 0.5) serif").as_deref(), Some("line-height: calc(1 + 0.5)"));
+	for css in ["font: 12px/-1 serif", "font: 12px/-2px serif", "font: 12px/auto serif", "font: 12px/red serif"] {
+	    assert!(expanded(css).is_empty(), "{css}");
+	}
+    }
+
+    #[test]
+    fn backgrounds() {
+	assert_eq!(expanded("background: red"),
+		   ["background-image: none", "background-position: 0% 0%", "background-size: auto", "background-repeat: repeat",
+		    "background-attachment: scroll", "background-origin: padding-box", "background-clip: border-box", "background-color: red"]);
+	// layers are kept apart with commas, and only the last one has a color
+	assert_eq!(expanded("background: url(a.png) no-repeat center / cover content-box, blue"),
+		   ["background-image: url(a.png), none", "background-position: center, 0% 0%", "background-size: cover, auto",
+		    "background-repeat: no-repeat, repeat", "background-attachment: scroll, scroll",
+		    "background-origin: content-box, padding-box", "background-clip: content-box, border-box", "background-color: blue"]);
+	assert!(expanded("background: red, url(a.png)").is_empty());
+	assert!(expanded("background: left / 10px 10px 10px").is_empty());
+    }
+
+    #[test]
+    fn list_styles() {
+	assert_eq!(expanded("list-style: none"), ["list-style-position: initial", "list-style-image: none", "list-style-type: none"]);
+	assert_eq!(expanded("list-style: square inside"), ["list-style-position: inside", "list-style-image: initial", "list-style-type: square"]);
+	assert_eq!(expanded("list-style: none url(a.png)"), ["list-style-position: initial", "list-style-image: url(a.png)", "list-style-type: none"]);
+	assert!(expanded("list-style: none none none").is_empty());
+    }
+
+    #[test]
+    fn keywords_vars_and_important() {
+	assert_eq!(expanded("padding: inherit"), ["padding-top: inherit", "padding-right: inherit", "padding-bottom: inherit", "padding-left: inherit"]);
+	assert!(expanded("padding: 1px inherit").is_empty());
+	// var()s are kept whole until they can be substituted
+	let declarations = parse_declaration_list("margin: var(--x) 1px");
+	assert_eq!(declarations.len(), 4);
+	assert!(declarations.iter().all(|d| d.shorthand.as_deref() == Some("margin") && serialize(&d.value).trim() == "var(--x) 1px"));
+	assert_eq!(longhand("margin", "2px 1px", "margin-right").as_deref(), Some("1px"));
+	assert!(parse_declaration_list("padding: 1px !important").iter().all(|d| d.important));
+	// longhands are left alone
+	assert_eq!(expanded("padding-top: 1px"), ["padding-top: 1px"]);
+    }
+}
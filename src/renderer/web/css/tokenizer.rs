@@ -0,0 +1,438 @@
+// turns css into tokens, following https://www.w3.org/TR/css-syntax-3/#tokenization
+
+// pieces of css that the tokenizer finds
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Ident(String),
+    // name of a function, like "rgb" in rgb(0, 0, 0)
+    Function(String),
+    // @media, etc. (without the @)
+    AtKeyword(String),
+    // #name, and whether it's a valid id
+    Hash(String, bool),
+    String(String),
+    // string with a newline in it
+    BadString,
+    // unquoted url(...)
+    Url(String),
+    BadUrl,
+    Delim(char),
+    // value, and whether it was written as an integer
+    Number(f64, bool),
+    Percentage(f64),
+    // value, whether it was written as an integer, and the unit
+    Dimension(f64, bool, String),
+    Whitespace,
+    // <!-- and -->
+    Cdo,
+    Cdc,
+    Colon,
+    Semicolon,
+    Comma,
+    OpenSquare,
+    CloseSquare,
+    OpenParen,
+    CloseParen,
+    OpenCurly,
+    CloseCurly,
+}
+
+// writes a token back out as css
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+	match self {
+	    Token::Ident(name) => write!(f, "{}", name),
+	    Token::Function(name) => write!(f, "{}(", name),
+	    Token::AtKeyword(name) => write!(f, "@{}", name),
+	    Token::Hash(name, _) => write!(f, "#{}", name),
+	    Token::String(text) => write!(f, "\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\a ")),
+	    Token::BadString => Ok(()),
+	    Token::Url(url) => write!(f, "url({})", url),
+	    Token::BadUrl => write!(f, "url()"),
+	    Token::Delim(c) => write!(f, "{}", c),
+	    Token::Number(value, _) => write!(f, "{}", value),
+	    Token::Percentage(value) => write!(f, "{}%", value),
+	    Token::Dimension(value, _, unit) => write!(f, "{}{}", value, unit),
+	    Token::Whitespace => write!(f, " "),
+	    Token::Cdo => write!(f, "<!--"),
+	    Token::Cdc => write!(f, "-->"),
+	    Token::Colon => write!(f, ":"),
+	    Token::Semicolon => write!(f, ";"),
+	    Token::Comma => write!(f, ","),
+	    Token::OpenSquare => write!(f, "["),
+	    Token::CloseSquare => write!(f, "]"),
+	    Token::OpenParen => write!(f, "("),
+	    Token::CloseParen => write!(f, ")"),
+	    Token::OpenCurly => write!(f, "{{"),
+	    Token::CloseCurly => write!(f, "}}"),
+	}
+    }
+}
+
+pub fn tokenize(css: &str) -> Vec<Token> {
+    let mut tokenizer = Tokenizer::new(css);
+    let mut tokens = Vec::new();
+    while let Some(token) = tokenizer.next_token() {
+	tokens.push(token);
+    }
+    tokens
+}
+
+struct Tokenizer {
+    chars: Vec<char>,
+    position: usize,
+}
+impl Tokenizer {
+    fn new(css: &str) -> Tokenizer {
+	// newlines are normalized to \n, and null characters aren't allowed
+	let css = css.replace("\r\n", "\n").replace(['\r', '\u{c}'], "\n").replace('\0', "\u{fffd}");
+	Tokenizer{chars: css.chars().collect(), position: 0}
+    }
+    // looks at a character without using it up (\0 if there's nothing there)
+    fn peek(&self, offset: usize) -> char {
+	*self.chars.get(self.position+offset).unwrap_or(&'\0')
+    }
+    fn at_end(&self) -> bool {
+	self.position >= self.chars.len()
+    }
+    fn next_token(&mut self) -> Option<Token> {
+	self.skip_comments();
+	if self.at_end() {
+	    return None;
+	}
+	let c = self.peek(0);
+	self.position += 1;
+	let token = match c {
+	    _ if is_whitespace(c) => {
+		while is_whitespace(self.peek(0)) && !self.at_end() {
+		    self.position += 1;
+		}
+		Token::Whitespace
+	    },
+	    '"' | '\'' => self.consume_string(c),
+	    '#' => {
+		if is_name(self.peek(0)) || is_valid_escape(self.peek(0), self.peek(1)) {
+		    let is_id = starts_identifier(self.peek(0), self.peek(1), self.peek(2));
+		    Token::Hash(self.consume_name(), is_id)
+		} else {
+		    Token::Delim('#')
+		}
+	    },
+	    '(' => Token::OpenParen,
+	    ')' => Token::CloseParen,
+	    '[' => Token::OpenSquare,
+	    ']' => Token::CloseSquare,
+	    '{' => Token::OpenCurly,
+	    '}' => Token::CloseCurly,
+	    ',' => Token::Comma,
+	    ':' => Token::Colon,
+	    ';' => Token::Semicolon,
+	    '+' | '.' if starts_number(c, self.peek(0), self.peek(1)) => {
+		self.position -= 1;
+		self.consume_numeric()
+	    },
+	    '-' => {
+		if starts_number(c, self.peek(0), self.peek(1)) {
+		    self.position -= 1;
+		    self.consume_numeric()
+		} else if self.peek(0) == '-' && self.peek(1) == '>' {
+		    self.position += 2;
+		    Token::Cdc
+		} else if starts_identifier(c, self.peek(0), self.peek(1)) {
+		    self.position -= 1;
+		    self.consume_ident_like()
+		} else {
+		    Token::Delim(c)
+		}
+	    },
+	    '<' if self.peek(0) == '!' && self.peek(1) == '-' && self.peek(2) == '-' => {
+		self.position += 3;
+		Token::Cdo
+	    },
+	    '@' if starts_identifier(self.peek(0), self.peek(1), self.peek(2)) => Token::AtKeyword(self.consume_name()),
+	    '\\' if is_valid_escape(c, self.peek(0)) => {
+		self.position -= 1;
+		self.consume_ident_like()
+	    },
+	    _ if c.is_ascii_digit() => {
+		self.position -= 1;
+		self.consume_numeric()
+	    },
+	    _ if is_name_start(c) => {
+		self.position -= 1;
+		self.consume_ident_like()
+	    },
+	    _ => Token::Delim(c),
+	};
+	Some(token)
+    }
+    fn skip_comments(&mut self) {
+	while self.peek(0) == '/' && self.peek(1) == '*' {
+	    self.position += 2;
+	    while !self.at_end() {
+		if self.peek(0) == '*' && self.peek(1) == '/' {
+		    self.position += 2;
+		    break;
+		}
+		self.position += 1;
+	    }
+	}
+    }
+    fn consume_string(&mut self, quote: char) -> Token {
+	let mut text = String::new();
+	while !self.at_end() {
+	    let c = self.peek(0);
+	    self.position += 1;
+	    match c {
+		_ if c == quote => return Token::String(text),
+		// the newline is left for the next token
+		'\n' => {
+		    self.position -= 1;
+		    return Token::BadString;
+		},
+		'\\' => {
+		    if self.at_end() {
+			continue;
+		    }
+		    // an escaped newline continues the string on the next line
+		    if self.peek(0) == '\n' {
+			self.position += 1;
+		    } else {
+			text.push(self.consume_escape());
+		    }
+		},
+		_ => text.push(c),
+	    }
+	}
+	Token::String(text)
+    }
+    // reads what's after a \
+    fn consume_escape(&mut self) -> char {
+	if self.at_end() {
+	    return '\u{fffd}';
+	}
+	let c = self.peek(0);
+	self.position += 1;
+	if !c.is_ascii_hexdigit() {
+	    return c;
+	}
+	let mut hex = String::from(c);
+	while hex.len() < 6 && self.peek(0).is_ascii_hexdigit() {
+	    hex.push(self.peek(0));
+	    self.position += 1;
+	}
+	// one whitespace character can end the escape
+	if is_whitespace(self.peek(0)) && !self.at_end() {
+	    self.position += 1;
+	}
+	match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+	    Some(c) if c != '\0' => c,
+	    _ => '\u{fffd}',
+	}
+    }
+    fn consume_name(&mut self) -> String {
+	let mut name = String::new();
+	loop {
+	    let c = self.peek(0);
+	    if is_name(c) && !self.at_end() {
+		name.push(c);
+		self.position += 1;
+	    } else if is_valid_escape(c, self.peek(1)) {
+		self.position += 1;
+		name.push(self.consume_escape());
+	    } else {
+		return name;
+	    }
+	}
+    }
+    fn consume_number(&mut self) -> (f64, bool) {
+	let mut number = String::new();
+	let mut integer = true;
+	if self.peek(0) == '+' || self.peek(0) == '-' {
+	    number.push(self.peek(0));
+	    self.position += 1;
+	}
+	self.consume_digits(&mut number);
+	if self.peek(0) == '.' && self.peek(1).is_ascii_digit() {
+	    number.push('.');
+	    self.position += 1;
+	    self.consume_digits(&mut number);
+	    integer = false;
+	}
+	// exponent, like 1e3
+	let exponent_sign = self.peek(1) == '+' || self.peek(1) == '-';
+	if (self.peek(0) == 'e' || self.peek(0) == 'E')
+	    && (self.peek(1).is_ascii_digit() || (exponent_sign && self.peek(2).is_ascii_digit())) {
+	    number.push('e');
+	    self.position += 1;
+	    if exponent_sign {
+		number.push(self.peek(0));
+		self.position += 1;
+	    }
+	    self.consume_digits(&mut number);
+	    integer = false;
+	}
+	(number.parse().unwrap_or(0.0), integer)
+    }
+    fn consume_digits(&mut self, number: &mut String) {
+	while self.peek(0).is_ascii_digit() {
+	    number.push(self.peek(0));
+	    self.position += 1;
+	}
+    }
+    fn consume_numeric(&mut self) -> Token {
+	let (value, integer) = self.consume_number();
+	if starts_identifier(self.peek(0), self.peek(1), self.peek(2)) {
+	    Token::Dimension(value, integer, self.consume_name())
+	} else if self.peek(0) == '%' {
+	    self.position += 1;
+	    Token::Percentage(value)
+	} else {
+	    Token::Number(value, integer)
+	}
+    }
+    fn consume_ident_like(&mut self) -> Token {
+	let name = self.consume_name();
+	if self.peek(0) != '(' {
+	    return Token::Ident(name);
+	}
+	self.position += 1;
+	if !name.eq_ignore_ascii_case("url") {
+	    return Token::Function(name);
+	}
+	// url("...") is a normal function, url(...) isn't
+	let mut whitespace = 0;
+	while is_whitespace(self.peek(whitespace)) && self.position+whitespace < self.chars.len() {
+	    whitespace += 1;
+	}
+	if self.peek(whitespace) == '"' || self.peek(whitespace) == '\'' {
+	    self.position += whitespace;
+	    return Token::Function(name);
+	}
+	self.consume_url()
+    }
+    fn consume_url(&mut self) -> Token {
+	let mut url = String::new();
+	while is_whitespace(self.peek(0)) && !self.at_end() {
+	    self.position += 1;
+	}
+	while !self.at_end() {
+	    let c = self.peek(0);
+	    self.position += 1;
+	    match c {
+		')' => return Token::Url(url),
+		_ if is_whitespace(c) => {
+		    while is_whitespace(self.peek(0)) && !self.at_end() {
+			self.position += 1;
+		    }
+		    if self.at_end() || self.peek(0) == ')' {
+			self.position = (self.position+1).min(self.chars.len());
+			return Token::Url(url);
+		    }
+		    self.consume_bad_url();
+		    return Token::BadUrl;
+		},
+		'"' | '\'' | '(' => {
+		    self.consume_bad_url();
+		    return Token::BadUrl;
+		},
+		_ if is_non_printable(c) => {
+		    self.consume_bad_url();
+		    return Token::BadUrl;
+		},
+		'\\' => {
+		    if is_valid_escape(c, self.peek(0)) {
+			url.push(self.consume_escape());
+		    } else {
+			self.consume_bad_url();
+			return Token::BadUrl;
+		    }
+		},
+		_ => url.push(c),
+	    }
+	}
+	Token::Url(url)
+    }
+    // skips the rest of a broken url
+    fn consume_bad_url(&mut self) {
+	while !self.at_end() {
+	    let c = self.peek(0);
+	    self.position += 1;
+	    if c == ')' {
+		return;
+	    }
+	    if is_valid_escape(c, self.peek(0)) {
+		self.consume_escape();
+	    }
+	}
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n'
+}
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+fn is_name(c: char) -> bool {
+    is_name_start(c) || c.is_ascii_digit() || c == '-'
+}
+fn is_non_printable(c: char) -> bool {
+    matches!(c, '\0'..='\u{8}' | '\u{b}' | '\u{e}'..='\u{1f}' | '\u{7f}')
+}
+fn is_valid_escape(first: char, second: char) -> bool {
+    first == '\\' && second != '\n' && second != '\0'
+}
+fn starts_identifier(first: char, second: char, third: char) -> bool {
+    match first {
+	'-' => is_name_start(second) || second == '-' || is_valid_escape(second, third),
+	'\\' => is_valid_escape(first, second),
+	_ => is_name_start(first),
+    }
+}
+fn starts_number(first: char, second: char, third: char) -> bool {
+    match first {
+	'+' | '-' => second.is_ascii_digit() || (second == '.' && third.is_ascii_digit()),
+	'.' => second.is_ascii_digit(),
+	_ => first.is_ascii_digit(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_and_functions() {
+	assert_eq!(tokenize("a:hover"), [Token::Ident("a".to_string()), Token::Colon, Token::Ident("hover".to_string())]);
+	assert_eq!(tokenize("rgb(@media"), [Token::Function("rgb".to_string()), Token::AtKeyword("media".to_string())]);
+	assert_eq!(tokenize("-moz-x --var \\31 a"), [Token::Ident("-moz-x".to_string()), Token::Whitespace, Token::Ident("--var".to_string()), Token::Whitespace, Token::Ident("1a".to_string())]);
+	assert_eq!(tokenize("#a1 #1a"), [Token::Hash("a1".to_string(), true), Token::Whitespace, Token::Hash("1a".to_string(), false)]);
+    }
+
+    #[test]
+    fn numbers() {
+	assert_eq!(tokenize("12px 50% -1.5 +3e2"), [Token::Dimension(12.0, true, "px".to_string()), Token::Whitespace,
+						     Token::Percentage(50.0), Token::Whitespace,
+						     Token::Number(-1.5, false), Token::Whitespace,
+						     Token::Number(300.0, false)]);
+	// a dot or sign on its own isn't a number
+	assert_eq!(tokenize(". -"), [Token::Delim('.'), Token::Whitespace, Token::Delim('-')]);
+    }
+
+    #[test]
+    fn strings_and_urls() {
+	assert_eq!(tokenize("'a;}b' \"c\\\"d\""), [Token::String("a;}b".to_string()), Token::Whitespace, Token::String("c\"d".to_string())]);
+	assert_eq!(tokenize("'a\nb'")[0], Token::BadString);
+	assert_eq!(tokenize("url( a;b.png )"), [Token::Url("a;b.png".to_string())]);
+	assert_eq!(tokenize("url(a b)"), [Token::BadUrl]);
+	// quoted urls are functions
+	assert_eq!(tokenize("url('a')"), [Token::Function("url".to_string()), Token::String("a".to_string()), Token::CloseParen]);
+    }
+
+    #[test]
+    fn comments_are_dropped() {
+	assert_eq!(tokenize("a/* } */b"), [Token::Ident("a".to_string()), Token::Ident("b".to_string())]);
+	assert_eq!(tokenize("<!-- -->"), [Token::Cdo, Token::Whitespace, Token::Cdc]);
+    }
+}
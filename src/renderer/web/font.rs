@@ -0,0 +1,202 @@
+use crate::rules;
+use crate::renderer::web::css::{self, tokenizer::Token, ComponentValue, Declaration};
+use crate::renderer::web::style::{self, FontStyle};
+
+use cairo::{FontFace, FtSynthesize};
+use std::rc::Rc;
+use std::cell::OnceCell;
+
+// an @font-face rule, which says where to get a font for a family, weight and style
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFaceRule {
+    pub family: String,
+    // urls to try, in order (relative to the stylesheet until it's loaded)
+    pub sources: Vec<String>,
+    // the weights the font is used for, from lowest to highest
+    pub weight: (u16, u16),
+    pub style: FontStyle,
+}
+
+// parses the declarations of an @font-face rule, which needs a family and somewhere to get the font from
+pub fn parse_font_face(declarations: &[Declaration]) -> Option<FontFaceRule> {
+    let mut family = None;
+    let mut sources = Vec::new();
+    let mut weight = (400, 400);
+    let mut style = FontStyle::Normal;
+    for declaration in declarations {
+	let values = declaration.value.iter().filter(|v| v != &&ComponentValue::Token(Token::Whitespace)).cloned().collect::<Vec<ComponentValue>>();
+	match declaration.name.as_str() {
+	    // there can only be one family
+	    "font-family" => family = parse_family_names(&values).filter(|families| families.len() == 1).map(|mut families| families.remove(0)),
+	    "src" => sources = values.split(|v| v == &ComponentValue::Token(Token::Comma)).filter_map(parse_source).collect(),
+	    "font-weight" => weight = parse_weight_range(&values).unwrap_or(weight),
+	    // oblique can have an angle after it, which doesn't matter here
+	    "font-style" => if let Some(ComponentValue::Token(Token::Ident(font_style))) = values.first() {
+		style = style::parse_font_style(font_style).unwrap_or(style);
+	    },
+	    _ => {},
+	}
+    }
+    if sources.is_empty() {
+	return None;
+    }
+    Some(FontFaceRule{family: family?, sources, weight, style})
+}
+
+// parses a url in src, like url(font.woff2) format("woff2")
+// (local() needs fonts installed on the computer to be looked up, which isn't supported)
+fn parse_source(values: &[ComponentValue]) -> Option<String> {
+    let url = css::parse_url(values.first()?)?;
+    match values.get(1) {
+	None => Some(url),
+	// formats that freetype can't read are skipped, so the next source is tried instead
+	Some(ComponentValue::Function(name, format)) if name.eq_ignore_ascii_case("format") => match format.as_slice() {
+	    [ComponentValue::Token(Token::String(format) | Token::Ident(format))] if FORMATS.contains(&format.to_ascii_lowercase().as_str()) => Some(url),
+	    _ => None,
+	},
+	_ => None,
+    }
+}
+
+// font formats that freetype can read
+const FORMATS: [&str;5] = ["truetype", "opentype", "woff", "woff2", "collection"];
+
+// parses a weight, or a range of weights like "100 900"
+fn parse_weight_range(values: &[ComponentValue]) -> Option<(u16, u16)> {
+    let weight = |value: &ComponentValue| match value {
+	ComponentValue::Token(Token::Number(n, _)) if (1.0..=1000.0).contains(n) => Some(*n as u16),
+	ComponentValue::Token(Token::Ident(keyword)) if keyword.eq_ignore_ascii_case("normal") => Some(400),
+	ComponentValue::Token(Token::Ident(keyword)) if keyword.eq_ignore_ascii_case("bold") => Some(700),
+	_ => None,
+    };
+    match values {
+	[ComponentValue::Token(Token::Ident(keyword))] if keyword.eq_ignore_ascii_case("auto") => Some((1, 1000)),
+	[single] => weight(single).map(|weight| (weight, weight)),
+	// ranges that are backwards are flipped around
+	[low, high] => {
+	    let (low, high) = (weight(low)?, weight(high)?);
+	    Some((low.min(high), low.max(high)))
+	},
+	_ => None,
+    }
+}
+
+// parses a comma separated list of font families, where names can be strings or words
+// (whitespace has to be taken out already, since words are put back together with single spaces)
+pub fn parse_family_names(values: &[ComponentValue]) -> Option<Vec<String>> {
+    values.split(|v| v == &ComponentValue::Token(Token::Comma)).map(|family| match family {
+	[ComponentValue::Token(Token::String(name))] => Some(name.to_string()),
+	[] => None,
+	words => words.iter().map(|word| match word {
+	    ComponentValue::Token(Token::Ident(word)) => Some(word.as_str()),
+	    _ => None,
+	}).collect::<Option<Vec<&str>>>().map(|words| words.join(" ")),
+    }).collect()
+}
+
+// a font that was downloaded for an @font-face rule (or the default font)
+pub struct Font {
+    pub family: String,
+    pub weight: (u16, u16),
+    pub style: FontStyle,
+    // freetype measures the text, and cairo draws it
+    pub face: freetype::Face,
+    // cairo faces for drawing it as it is, bolder, slanted, and bolder and slanted
+    cairo_faces: Vec<FontFace>,
+}
+impl Font {
+    fn new(family: String, weight: (u16, u16), style: FontStyle, face: freetype::Face) -> Option<Font> {
+	let synthesize = [vec![], vec![FtSynthesize::Bold], vec![FtSynthesize::Oblique], vec![FtSynthesize::Bold, FtSynthesize::Oblique]];
+	let cairo_faces = synthesize.into_iter().map(|flags| {
+	    let cairo_face = FontFace::create_from_ft(&face).ok()?;
+	    for flag in flags {
+		cairo_face.set_synthesize(flag);
+	    }
+	    Some(cairo_face)
+	}).collect::<Option<Vec<FontFace>>>()?;
+	Some(Font{family, weight, style, face, cairo_faces})
+    }
+    // the cairo face to draw text with, making it bolder or slanted if the font isn't
+    pub fn cairo_face(&self, weight: u16, style: FontStyle) -> &FontFace {
+	let bold = weight >= 600 && self.weight.1 < 600;
+	let oblique = style != FontStyle::Normal && self.style == FontStyle::Normal;
+	&self.cairo_faces[bold as usize + 2*oblique as usize]
+    }
+}
+
+// the fonts a document has loaded
+#[derive(Default)]
+pub struct Fonts {
+    fonts: Vec<Font>,
+    // the font for text that no @font-face rule fits, loaded the first time it's needed (None if it couldn't be)
+    default: OnceCell<Option<Font>>,
+    // freetype, started the first time it's needed (None if it couldn't be)
+    library: OnceCell<Option<freetype::Library>>,
+}
+impl Fonts {
+    // removes every font, for when the stylesheets change
+    pub fn clear(&mut self) {
+	self.fonts.clear();
+    }
+    // reads a downloaded font file for an @font-face rule, returning whether freetype could read it
+    pub fn add(&mut self, rule: &FontFaceRule, data: Rc<Vec<u8>>) -> bool {
+	let font = self.library()
+	    .and_then(|library| library.new_memory_face(data, 0).ok())
+	    .and_then(|face| Font::new(rule.family.clone(), rule.weight, rule.style, face));
+	match font {
+	    Some(font) => {
+		self.fonts.push(font);
+		true
+	    },
+	    None => false,
+	}
+    }
+    // finds the font that best matches the first family in a list that has any fonts, or the default font if none of them do
+    // (None if there isn't a default font either)
+    pub fn find(&self, families: &[String], weight: u16, style: FontStyle) -> Option<&Font> {
+	self.find_loaded(families, weight, style).or_else(|| self.default_font())
+    }
+    fn library(&self) -> Option<&freetype::Library> {
+	self.library.get_or_init(|| freetype::Library::init().ok()).as_ref()
+    }
+    fn default_font(&self) -> Option<&Font> {
+	self.default.get_or_init(|| {
+	    let face = self.library()?.new_face(rules::DEFAULT_FONT, 0).ok()?;
+	    Font::new(String::new(), (400, 400), FontStyle::Normal, face)
+	}).as_ref()
+    }
+    // finds the @font-face font that best matches the first family in a list that has any
+    fn find_loaded(&self, families: &[String], weight: u16, style: FontStyle) -> Option<&Font> {
+	families.iter().find_map(|family| {
+	    let fonts = self.fonts.iter().filter(|font| font.family.eq_ignore_ascii_case(family)).collect::<Vec<&Font>>();
+	    // italic and oblique can stand in for each other, and normal can stand in for both
+	    let styles = match style {
+		FontStyle::Normal => [FontStyle::Normal, FontStyle::Oblique, FontStyle::Italic],
+		FontStyle::Italic => [FontStyle::Italic, FontStyle::Oblique, FontStyle::Normal],
+		FontStyle::Oblique => [FontStyle::Oblique, FontStyle::Italic, FontStyle::Normal],
+	    };
+	    let style = styles.into_iter().find(|style| fonts.iter().any(|font| font.style == *style))?;
+	    fonts.into_iter().filter(|font| font.style == style).min_by_key(|font| weight_distance(font.weight, weight))
+	})
+    }
+}
+
+// how far a font's weights are from the one that's wanted, following https://www.w3.org/TR/css-fonts-4/#font-style-matching
+// (light weights look for lighter fonts first, bold ones look for bolder ones, and weights in between look up to 500 first)
+fn weight_distance((low, high): (u16, u16), weight: u16) -> (u8, u16) {
+    if (low..=high).contains(&weight) {
+	(0, 0)
+    } else if weight < low {
+	match weight {
+	    400..=500 if low <= 500 => (1, low-weight),
+	    0..=399 => (2, low-weight),
+	    400..=500 => (3, low-weight),
+	    _ => (1, low-weight),
+	}
+    } else {
+	match weight {
+	    0..=399 => (1, weight-high),
+	    _ => (2, weight-high),
+	}
+    }
+}
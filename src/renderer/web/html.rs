@@ -1,150 +1,753 @@
 use crate::rules;
 use crate::renderer::layout::LayoutBox;
+use crate::renderer::web::tokenizer::{Tokenizer, Token, Tag, State};
+use crate::renderer::web::css::{self, Declaration, StyleRule};
+use crate::renderer::web::style::ComputedStyle;
+use crate::renderer::web::css::media::Device;
+use crate::renderer::web::css::cascade::{cascade, CascadedDeclaration, Origin};
+use crate::renderer::web::css::selector::{self, Selector, SimpleSelector, PseudoClass, Combinator, AttributeOperator, Specificity};
 
 use std::collections::HashMap;
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::cell::{Cell, RefCell};
 
 pub enum NodeType {
     // string inside of text
-    Text(String),
-    // container type, children, and params
-    Container(String, RefCell<Vec<Rc<Node>>>, HashMap<String, String>),
+    Text(RefCell<String>),
+    // container type, children, params (in the order they were written), and namespace
+    Container(String, RefCell<Vec<Rc<Node>>>, RefCell<Vec<(String, String)>>, Namespace),
     // document
-    Document(RefCell<Vec<Rc<Node>>>)
+    Document(RefCell<Vec<Rc<Node>>>),
+    // text inside of <!-- -->
+    Comment(RefCell<String>),
+    // doctype name
+    Doctype(String),
+}
+// what kind of markup an element is
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Namespace {
+    Html,
+    Svg,
+    MathMl,
 }
 pub struct Node {
     // whether node is text or container
     pub node_type: NodeType,
-    // parent node
-    parent: RefCell<Option<Rc<Node>>>,
-    // css properties
-    pub css: RefCell<HashMap<String, String>>,
+    // parent node (weak so that parents and children don't keep each other alive)
+    parent: RefCell<Weak<Node>>,
+    // css properties that the cascade picked
+    pub css: RefCell<HashMap<String, Declaration>>,
+    // what those properties work out to, with inheritance
+    pub style: RefCell<ComputedStyle>,
     // layout render
     pub render: Rc<RefCell<LayoutBox>>,
+    // what needs to be recalculated after changes
+    pub dirty: Cell<Dirty>,
+    // what the user is doing with this element
+    pub state: Cell<ElementState>,
+}
+// where css for a document comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum StyleSource {
+    // text inside of <style>, and its media attribute
+    Inline(String, String),
+    // href of <link rel="stylesheet">, and its media attribute
+    Link(String, String),
+}
+// information about a document from its <head>, with urls as they are written
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub base_url: Option<String>,
+    pub favicon: Option<String>,
+    pub charset: Option<String>,
+    pub description: Option<String>,
+    pub canonical: Option<String>,
+}
+// which parts of a node are out of date
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Dirty {
+    // css needs to be recalculated
+    pub style: bool,
+    // layout box needs to be recalculated
+    pub layout: bool,
+    // css of something below this node needs to be recalculated
+    pub descendant_style: bool,
+    // layout box of something below this node needs to be recalculated
+    pub descendant_layout: bool,
+}
+// what the user is doing with an element, for pseudo-classes like :hover
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ElementState {
+    // the pointer is over it (or something inside of it)
+    pub hover: bool,
+    // it (or something inside of it) is being clicked
+    pub active: bool,
+    pub focus: bool,
+    // it's a link to a page in the history
+    pub visited: bool,
+}
+impl Dirty {
+    // everything needs to be calculated (for new nodes)
+    pub fn all() -> Dirty {
+	Dirty{style: true, layout: true, descendant_style: true, descendant_layout: true}
+    }
 }
 impl Node {
     // empty document
     fn get_document() -> Node {
-	return Node{node_type: NodeType::Document(RefCell::new(Vec::new())), parent: RefCell::new(None), css: RefCell::new(HashMap::new()), render: Rc::new(RefCell::new(LayoutBox::empty()))}
-    }
-    // get new container node from tag
-    fn from_tag(tag_content: String) -> Node {
-	let mut parts = Vec::new();
-	let mut word = String::from("");
-	let mut in_str = false;
-	let mut str_char = '\"';
-	let mut str_ignore_next = false;
-	for c in tag_content.chars() {
-	    if in_str {
-		if c == str_char && !str_ignore_next {
-		    in_str = true;
-		} else if c == '\\' && !str_ignore_next {
-		    str_ignore_next = true;
-		} else {
-		    word.push(c);
-		    str_ignore_next = false;
-		}
-	    } else {
-		if c == '\'' || c == '\"' {
-		    in_str = true;
-		    str_char = c.clone();
-		} else if c == ' ' {
-		    parts.push(word.clone());
-		    word = "".to_string();
-		} else {
-		    word.push(c);
-		}
-	    }
-	}
-	parts.push(word.clone());
-	let tag_name = parts.remove(0);
-	let mut params = HashMap::new();
-	for param in parts {
-	    let param_parts = param.splitn(2, "=").collect::<Vec<&str>>();
-	    // make sure that this is actually a parameter
-	    if param_parts.len() == 2 {
-		params.insert(param_parts[0].to_string(), param_parts[1].to_string());
-	    }
-	}
-	Node{node_type: NodeType::Container(tag_name.to_string(), RefCell::new(Vec::new()), params), parent: RefCell::new(None), css: RefCell::new(HashMap::new()), render: Rc::new(RefCell::new(LayoutBox::empty()))}
+	return Node{node_type: NodeType::Document(RefCell::new(Vec::new())), parent: RefCell::new(Weak::new()), css: RefCell::new(HashMap::new()), style: RefCell::new(ComputedStyle::default()), render: Rc::new(RefCell::new(LayoutBox::empty())), dirty: Cell::new(Dirty::all()), state: Cell::new(ElementState::default())}
+    }
+    // get new container node from tag name and attributes
+    fn from_tag(tag_name: String, params: Vec<(String, String)>, namespace: Namespace) -> Node {
+	Node{node_type: NodeType::Container(tag_name, RefCell::new(Vec::new()), RefCell::new(params), namespace), parent: RefCell::new(Weak::new()), css: RefCell::new(HashMap::new()), style: RefCell::new(ComputedStyle::default()), render: Rc::new(RefCell::new(LayoutBox::empty())), dirty: Cell::new(Dirty::all()), state: Cell::new(ElementState::default())}
     }
     // gets children, if there are any
     pub fn children(&self) -> &RefCell<Vec<Rc<Node>>> {
 	match &self.node_type {
 	    NodeType::Document(children) => children,
-	    NodeType::Container(_, children, _) => children,
-	    NodeType::Text(_) => panic!("Node has no children!"),
+	    NodeType::Container(_, children, _, _) => children,
+	    _ => panic!("Node has no children!"),
 	}
     }
+    // whether this is a document or element, which are the only nodes that can have children
+    fn can_have_children(&self) -> bool {
+	matches!(self.node_type, NodeType::Document(_) | NodeType::Container(..))
+    }
     // get new text node from text
     fn from_text(text: String) -> Node {
-	return Node{node_type: NodeType::Text(text), parent: RefCell::new(None), css: RefCell::new(HashMap::new()), render: Rc::new(RefCell::new(LayoutBox::empty()))}
+	Node::from_type(NodeType::Text(RefCell::new(text)))
+    }
+    // get new node that isn't a document or element
+    fn from_type(node_type: NodeType) -> Node {
+	Node{node_type, parent: RefCell::new(Weak::new()), css: RefCell::new(HashMap::new()), style: RefCell::new(ComputedStyle::default()), render: Rc::new(RefCell::new(LayoutBox::empty())), dirty: Cell::new(Dirty::all()), state: Cell::new(ElementState::default())}
     }
     // checks if container node has end tag
     fn is_empty_element(&self) -> bool {
 	match &self.node_type {
-	    NodeType::Container(tag_name, _, _) => rules::EMPTY_ELEMENTS.iter().any(|e| e==&tag_name),
+	    NodeType::Container(tag_name, _, _, Namespace::Html) => rules::EMPTY_ELEMENTS.iter().any(|e| e==&tag_name),
 	    _ => false
 	}
     }
-    // gets css from <style> tags
-    pub fn find_css(&self) -> String {
-	let mut css = String::from("");
+    // gets css from <style> and <link rel="stylesheet"> tags, in document order
+    pub fn find_css(&self) -> Vec<StyleSource> {
+	let mut css = Vec::new();
 	match &self.node_type {
 	    NodeType::Document(children) => {
 		for child in children.borrow().iter() {
-		    css += &child.find_css();
+		    css.append(&mut child.find_css());
 		}
 	    },
-	    NodeType::Container(tag_name, children, _) => {
+	    NodeType::Container(tag_name, children, params, _) => {
+		let media = find_attribute(&params.borrow(), "media").cloned().unwrap_or_default();
 		if tag_name == &String::from("style") {
+		    let mut text = String::new();
 		    for child in children.borrow().iter() {
 			match &child.node_type {
-			    NodeType::Text(text) => css += text,
+			    NodeType::Text(t) => text += &t.borrow(),
 			    _ => {}
 			}
 		    }
+		    css.push(StyleSource::Inline(text, media));
+		} else if tag_name == "link" {
+		    let rel = find_attribute(&params.borrow(), "rel").cloned().unwrap_or_default().to_ascii_lowercase();
+		    let rel = rel.split_whitespace().collect::<Vec<&str>>();
+		    // alternate stylesheets aren't used unless the user picks them
+		    if rel.contains(&"stylesheet") && !rel.contains(&"alternate") {
+			if let Some(href) = find_attribute(&params.borrow(), "href") {
+			    css.push(StyleSource::Link(href.to_string(), media));
+			}
+		    }
 		} else {
 		    for child in children.borrow().iter() {
-			css += &child.find_css();
+			css.append(&mut child.find_css());
 		    }
 		}
 	    }
-	    NodeType::Text(_) => {},
+	    _ => {},
 	}
 	return css;
     }
-    // figures out if a basic selector (tag name, class name, id, etc.) applies
-    fn basic_selector_applies(&self, selector: String) -> bool {
+    // finds the title, base url, etc. of a document
+    pub fn metadata(&self) -> DocumentMetadata {
+	let mut metadata = DocumentMetadata::default();
+	// if something is there more than once, the first one is used
+	for element in self.descendant_elements() {
+	    match element.tag_name().unwrap_or("") {
+		// svg can have a <title> too
+		"title" if metadata.title.is_none() && element.namespace() == Some(Namespace::Html) => {
+		    let title = element.text_content().split_whitespace().collect::<Vec<&str>>().join(" ");
+		    metadata.title = Some(title);
+		},
+		"base" if metadata.base_url.is_none() => {
+		    metadata.base_url = element.get_attribute("href");
+		},
+		"link" => {
+		    let rel = element.get_attribute("rel").unwrap_or_default().to_ascii_lowercase();
+		    let rel = rel.split_whitespace().collect::<Vec<&str>>();
+		    if rel.contains(&"icon") && metadata.favicon.is_none() {
+			metadata.favicon = element.get_attribute("href");
+		    }
+		    if rel.contains(&"canonical") && metadata.canonical.is_none() {
+			metadata.canonical = element.get_attribute("href");
+		    }
+		},
+		"meta" => {
+		    let name = element.get_attribute("name").unwrap_or_default().to_ascii_lowercase();
+		    let http_equiv = element.get_attribute("http-equiv").unwrap_or_default().to_ascii_lowercase();
+		    if metadata.charset.is_none() {
+			if let Some(charset) = element.get_attribute("charset") {
+			    metadata.charset = Some(charset.trim().to_ascii_lowercase());
+			} else if http_equiv == "content-type" {
+			    // like <meta http-equiv="content-type" content="text/html; charset=utf-8">
+			    let content = element.get_attribute("content").unwrap_or_default().to_ascii_lowercase();
+			    if let Some((_, charset)) = content.split_once("charset=") {
+				let charset = charset.split(';').next().unwrap_or("");
+				metadata.charset = Some(charset.trim().trim_matches(['"', '\'']).to_string());
+			    }
+			}
+		    }
+		    if name == "description" && metadata.description.is_none() {
+			metadata.description = element.get_attribute("content");
+		    }
+		},
+		_ => {},
+	    }
+	}
+	metadata
+    }
+    // figures out if a simple selector (tag name, class name, id, etc.) applies
+    fn simple_selector_applies(&self, selector: &SimpleSelector, scope: Option<&Node>) -> bool {
 	match &self.node_type {
-	    NodeType::Container(tag_name, _, params) => {
-		if selector == "*" {
-		    return true;
-		} if selector.starts_with(".") {
-		    let class_selector = selector.split_at(1).1.to_string();
-		    return params.get("class") == Some(&class_selector);
-		} else if selector.starts_with("#") {
-		    let id_selector = selector.split_at(1).1.to_string();
-		    return params.get("id") == Some(&id_selector);
-		} else {
-		    return &selector == tag_name;
+	    NodeType::Container(tag_name, _, params, namespace) => {
+		let params = params.borrow();
+		match selector {
+		    SimpleSelector::Universal => true,
+		    SimpleSelector::Class(class) => self.class_list().contains(class),
+		    SimpleSelector::Id(id) => find_attribute(&params, "id") == Some(id),
+		    SimpleSelector::Attribute(name, value) => {
+			// html attribute names are lowercase, but svg and mathml ones can have capitals
+			let name = match namespace {
+			    Namespace::Html => name.to_ascii_lowercase(),
+			    _ => name.to_string(),
+			};
+			match (find_attribute(&params, &name), value) {
+			    (None, _) => false,
+			    (Some(_), None) => true,
+			    (Some(actual), Some((operator, expected, ignore_case))) => attribute_value_applies(actual, *operator, expected, *ignore_case),
+			}
+		    },
+		    // html tag names aren't case sensitive, but svg and mathml ones are
+		    SimpleSelector::Type(name) if namespace == &Namespace::Html => name.eq_ignore_ascii_case(tag_name),
+		    SimpleSelector::Type(name) => name == tag_name,
+		    SimpleSelector::PseudoClass(pseudo_class) => self.pseudo_class_applies(pseudo_class, scope),
 		}
 	    },
 	    _ => false
 	}
     }
+    // checks whether a pseudo-class like :first-child applies to this element
+    fn pseudo_class_applies(&self, pseudo_class: &PseudoClass, scope: Option<&Node>) -> bool {
+	let state = self.state.get();
+	match pseudo_class {
+	    PseudoClass::NthChild(a, b, from_end) => nth_applies(*a, *b, self.element_position(false, *from_end)),
+	    PseudoClass::NthOfType(a, b, from_end) => nth_applies(*a, *b, self.element_position(true, *from_end)),
+	    PseudoClass::OnlyChild => self.element_position(false, false) == 1 && self.element_position(false, true) == 1,
+	    PseudoClass::OnlyOfType => self.element_position(true, false) == 1 && self.element_position(true, true) == 1,
+	    PseudoClass::Not(selectors) => !selector_list_applies(self, selectors, scope),
+	    PseudoClass::Is(selectors) | PseudoClass::Where(selectors) => selector_list_applies(self, selectors, scope),
+	    PseudoClass::Has(selectors) => {
+		// relative selectors can only find things inside of this element or after it
+		let mut candidates = self.descendant_elements();
+		let mut sibling = self.next_element_sibling();
+		while let Some(s) = sibling {
+		    candidates.push(Rc::clone(&s));
+		    candidates.append(&mut s.descendant_elements());
+		    sibling = s.next_element_sibling();
+		}
+		candidates.iter().any(|candidate| selector_list_applies(candidate, selectors, Some(self)))
+	    },
+	    PseudoClass::Root => self.parent().is_some_and(|parent| matches!(parent.node_type, NodeType::Document(_))),
+	    // comments don't count, but text (even whitespace) does
+	    PseudoClass::Empty => self.children().borrow().iter().all(|child| match &child.node_type {
+		NodeType::Text(text) => text.borrow().is_empty(),
+		NodeType::Comment(_) => true,
+		_ => false,
+	    }),
+	    // without an element to start from (like in a stylesheet), :scope is the root
+	    PseudoClass::Scope => match scope {
+		Some(scope) if scope.is_element() => std::ptr::eq(self, scope),
+		_ => self.pseudo_class_applies(&PseudoClass::Root, None),
+	    },
+	    PseudoClass::Hover => state.hover,
+	    PseudoClass::Active => state.active,
+	    PseudoClass::Focus => state.focus,
+	    PseudoClass::Link => self.is_link() && !state.visited,
+	    PseudoClass::Visited => self.is_link() && state.visited,
+	}
+    }
+    // the position of this element among its sibling elements, counting from 1 (and only counting ones with the same tag name for of_type)
+    fn element_position(&self, of_type: bool, from_end: bool) -> usize {
+	// elements without a parent are on their own
+	let siblings = match self.siblings() {
+	    Some((siblings, _)) => siblings,
+	    None => return 1,
+	};
+	let siblings = siblings.iter()
+	    .filter(|s| s.is_element() && (!of_type || (s.tag_name() == self.tag_name() && s.namespace() == self.namespace())))
+	    .collect::<Vec<_>>();
+	let index = siblings.iter().position(|s| std::ptr::eq(&***s, self)).unwrap_or(0);
+	if from_end {
+	    siblings.len()-index
+	} else {
+	    index+1
+	}
+    }
+}
+// dom traversal and queries
+impl Node {
+    // gets the parent node, if there is one
+    pub fn parent(&self) -> Option<Rc<Node>> {
+	self.parent.borrow().upgrade()
+    }
+    // gets the siblings of this node (including itself) and its position among them
+    fn siblings(&self) -> Option<(Vec<Rc<Node>>, usize)> {
+	let parent = self.parent()?;
+	let siblings = parent.children().borrow().clone();
+	let index = siblings.iter().position(|s| std::ptr::eq(&**s, self))?;
+	Some((siblings, index))
+    }
+    // gets the node right before this one
+    pub fn previous_sibling(&self) -> Option<Rc<Node>> {
+	let (siblings, index) = self.siblings()?;
+	if index == 0 {
+	    None
+	} else {
+	    Some(Rc::clone(&siblings[index-1]))
+	}
+    }
+    // gets the node right after this one
+    pub fn next_sibling(&self) -> Option<Rc<Node>> {
+	let (siblings, index) = self.siblings()?;
+	siblings.get(index+1).map(Rc::clone)
+    }
+    // gets the closest element before this one
+    pub fn previous_element_sibling(&self) -> Option<Rc<Node>> {
+	let (siblings, index) = self.siblings()?;
+	siblings[..index].iter().rev().find(|s| s.is_element()).map(Rc::clone)
+    }
+    // gets the closest element after this one
+    pub fn next_element_sibling(&self) -> Option<Rc<Node>> {
+	let (siblings, index) = self.siblings()?;
+	siblings[index+1..].iter().find(|s| s.is_element()).map(Rc::clone)
+    }
+    // whether this node is an element (container)
+    pub fn is_element(&self) -> bool {
+	matches!(self.node_type, NodeType::Container(..))
+    }
+    // whether this is a link that can be visited (for :link and :visited)
+    pub fn is_link(&self) -> bool {
+	self.namespace() == Some(Namespace::Html) && matches!(self.tag_name(), Some("a" | "area")) && self.has_attribute("href")
+    }
+    // whether clicking on this element gives it focus
+    pub fn is_focusable(&self) -> bool {
+	self.is_link() || self.has_attribute("tabindex") || (self.namespace() == Some(Namespace::Html) && matches!(self.tag_name(), Some("input" | "select" | "textarea" | "button" | "iframe")))
+    }
+    // gets the tag name of an element
+    pub fn tag_name(&self) -> Option<&str> {
+	match &self.node_type {
+	    NodeType::Container(tag_name, _, _, _) => Some(tag_name),
+	    _ => None,
+	}
+    }
+    // gets the namespace of an element
+    pub fn namespace(&self) -> Option<Namespace> {
+	match &self.node_type {
+	    NodeType::Container(_, _, _, namespace) => Some(*namespace),
+	    _ => None,
+	}
+    }
+    // whether html can go inside of this svg or mathml element
+    fn is_html_integration_point(&self) -> bool {
+	match (self.namespace(), self.tag_name()) {
+	    (Some(Namespace::Svg), Some("foreignObject" | "desc" | "title")) => true,
+	    (Some(Namespace::MathMl), Some("annotation-xml")) => {
+		let encoding = self.get_attribute("encoding").unwrap_or_default().to_ascii_lowercase();
+		encoding == "text/html" || encoding == "application/xhtml+xml"
+	    },
+	    _ => false,
+	}
+    }
+    // whether this is a mathml element that holds text (and can have html tags in it)
+    fn is_mathml_text_integration_point(&self) -> bool {
+	self.namespace() == Some(Namespace::MathMl) && ["mi", "mo", "mn", "ms", "mtext"].contains(&self.tag_name().unwrap_or(""))
+    }
+    // gets the value of an attribute
+    pub fn get_attribute(&self, name: &str) -> Option<String> {
+	match &self.node_type {
+	    NodeType::Container(_, _, params, _) => find_attribute(&params.borrow(), name).cloned(),
+	    _ => None,
+	}
+    }
+    // checks if an element has an attribute
+    pub fn has_attribute(&self, name: &str) -> bool {
+	self.get_attribute(name).is_some()
+    }
+    // gets all the attributes of an element, in the order they were written
+    pub fn attributes(&self) -> Vec<(String, String)> {
+	match &self.node_type {
+	    NodeType::Container(_, _, params, _) => params.borrow().clone(),
+	    _ => Vec::new(),
+	}
+    }
+    // gets the id of an element
+    pub fn id(&self) -> Option<String> {
+	self.get_attribute("id")
+    }
+    // gets the classes of an element
+    pub fn class_list(&self) -> Vec<String> {
+	match self.get_attribute("class") {
+	    Some(classes) => classes.split_whitespace().map(|c| c.to_string()).collect(),
+	    None => Vec::new(),
+	}
+    }
+    // gets all the text inside of this node
+    pub fn text_content(&self) -> String {
+	match &self.node_type {
+	    NodeType::Text(text) => text.borrow().to_string(),
+	    NodeType::Document(children) | NodeType::Container(_, children, _, _) => children.borrow().iter().map(|c| c.text_content()).collect(),
+	    // comments aren't part of the text
+	    _ => String::new(),
+	}
+    }
+    // gets all the elements inside of this node, in document order
+    pub fn descendant_elements(&self) -> Vec<Rc<Node>> {
+	let mut elements = Vec::new();
+	if !self.can_have_children() {
+	    return elements;
+	}
+	for child in self.children().borrow().iter() {
+	    if child.is_element() {
+		elements.push(Rc::clone(child));
+		elements.append(&mut child.descendant_elements());
+	    }
+	}
+	elements
+    }
+    // finds the first element with an id
+    pub fn get_element_by_id(&self, id: &str) -> Option<Rc<Node>> {
+	self.descendant_elements().into_iter().find(|e| e.id().as_deref() == Some(id))
+    }
+    // finds all elements with a tag name ("*" matches everything)
+    pub fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<Rc<Node>> {
+	self.descendant_elements().into_iter().filter(|e| tag_name == "*" || e.tag_name() == Some(tag_name)).collect()
+    }
+    // finds all elements that have all of the (space separated) class names
+    pub fn get_elements_by_class_name(&self, class_names: &str) -> Vec<Rc<Node>> {
+	let class_names = class_names.split_whitespace().collect::<Vec<&str>>();
+	if class_names.is_empty() {
+	    return Vec::new();
+	}
+	self.descendant_elements().into_iter().filter(|e| {
+	    let class_list = e.class_list();
+	    class_names.iter().all(|name| class_list.iter().any(|c| c == name))
+	}).collect()
+    }
+    // finds the first element matching a css selector (none if the selector is invalid)
+    pub fn query_selector(&self, selectors: &str) -> Option<Rc<Node>> {
+	let selectors = selector::parse(selectors)?;
+	self.descendant_elements().into_iter().find(|e| selector_list_applies(e, &selectors, Some(self)))
+    }
+    // finds all elements matching a css selector
+    pub fn query_selector_all(&self, selectors: &str) -> Vec<Rc<Node>> {
+	let selectors = selector::parse(selectors).unwrap_or_default();
+	self.descendant_elements().into_iter().filter(|e| selector_list_applies(e, &selectors, Some(self))).collect()
+    }
+}
+// dom changes
+impl Node {
+    // creates a new element that isn't in a document yet
+    pub fn create_element(tag_name: &str) -> Rc<Node> {
+	Rc::new(Node::from_tag(tag_name.to_string(), Vec::new(), Namespace::Html))
+    }
+    // creates a new text node that isn't in a document yet
+    pub fn create_text_node(text: &str) -> Rc<Node> {
+	Rc::new(Node::from_text(text.to_string()))
+    }
+    // creates a new comment that isn't in a document yet
+    pub fn create_comment(text: &str) -> Rc<Node> {
+	Rc::new(Node::from_type(NodeType::Comment(RefCell::new(text.to_string()))))
+    }
+    // adds a node as the last child of this one, returning whether it could be
+    pub fn append_child(self: &Rc<Node>, child: Rc<Node>) -> bool {
+	self.insert_before(child, None)
+    }
+    // adds a node as a child right before another child (or at the end if there isn't one), returning whether it could be
+    // (only documents and elements can have children, and a node can't go inside of itself)
+    pub fn insert_before(self: &Rc<Node>, child: Rc<Node>, reference: Option<&Rc<Node>>) -> bool {
+	if !self.can_have_children() {
+	    return false;
+	}
+	let mut ancestor = Some(Rc::clone(self));
+	while let Some(node) = ancestor {
+	    if Rc::ptr_eq(&node, &child) {
+		return false;
+	    }
+	    ancestor = node.parent();
+	}
+	// a node can only be in one place at a time
+	if let Some(old_parent) = child.parent() {
+	    old_parent.remove_child(&child);
+	}
+	{
+	    let mut children = self.children().borrow_mut();
+	    let index = match reference.and_then(|r| children.iter().position(|c| Rc::ptr_eq(c, r))) {
+		Some(index) => index,
+		None => children.len(),
+	    };
+	    children.insert(index, Rc::clone(&child));
+	}
+	*child.parent.borrow_mut() = Rc::downgrade(self);
+	// selectors like :last-child and + can change for the siblings too
+	self.invalidate_style();
+	true
+    }
+    // removes a child from this node, returning whether it was there
+    pub fn remove_child(&self, child: &Rc<Node>) -> bool {
+	if !self.can_have_children() {
+	    return false;
+	}
+	let mut children = self.children().borrow_mut();
+	match children.iter().position(|c| Rc::ptr_eq(c, child)) {
+	    Some(index) => {
+		children.remove(index);
+		*child.parent.borrow_mut() = Weak::new();
+		self.invalidate_style();
+		true
+	    },
+	    None => false,
+	}
+    }
+    // adds a node the parser just made as the last child, only styling the new node
+    // (the parser restyles everything once it's done, for selectors like :last-child that depend on later siblings)
+    fn push_parsed_child(self: &Rc<Node>, child: Rc<Node>) {
+	self.children().borrow_mut().push(Rc::clone(&child));
+	*child.parent.borrow_mut() = Rc::downgrade(self);
+	child.invalidate_style();
+    }
+    // sets the value of an attribute
+    pub fn set_attribute(&self, name: &str, value: &str) {
+	if let NodeType::Container(_, _, params, _) = &self.node_type {
+	    let mut params = params.borrow_mut();
+	    // attributes that are already there keep their place
+	    match params.iter_mut().find(|(n, _)| n == name) {
+		Some((_, old_value)) => *old_value = value.to_string(),
+		None => params.push((name.to_string(), value.to_string())),
+	    }
+	    // selectors can look at any attribute
+	    self.invalidate_style();
+	}
+    }
+    // removes an attribute
+    pub fn remove_attribute(&self, name: &str) {
+	if let NodeType::Container(_, _, params, _) = &self.node_type {
+	    let index = params.borrow().iter().position(|(n, _)| n == name);
+	    if let Some(index) = index {
+		params.borrow_mut().remove(index);
+		self.invalidate_style();
+	    }
+	}
+    }
+    // sets the text of a text node or comment, or replaces the children of an element with text
+    pub fn set_text(self: &Rc<Node>, text: &str) {
+	match &self.node_type {
+	    NodeType::Text(t) | NodeType::Comment(t) => {
+		*t.borrow_mut() = text.to_string();
+		self.invalidate_layout();
+	    },
+	    NodeType::Doctype(_) => {},
+	    _ => {
+		let children = self.children().borrow().clone();
+		for child in children.iter() {
+		    self.remove_child(child);
+		}
+		if !text.is_empty() {
+		    self.append_child(Node::create_text_node(text));
+		}
+	    },
+	}
+    }
+    // marks this node as needing its css (and so its layout) recalculated
+    pub fn invalidate_style(&self) {
+	let mut dirty = self.dirty.get();
+	dirty.style = true;
+	dirty.layout = true;
+	self.dirty.set(dirty);
+	self.invalidate_ancestors(true);
+    }
+    // marks this node as needing its layout recalculated
+    pub fn invalidate_layout(&self) {
+	let mut dirty = self.dirty.get();
+	dirty.layout = true;
+	self.dirty.set(dirty);
+	self.invalidate_ancestors(false);
+    }
+    // lets ancestors know that something below them changed
+    fn invalidate_ancestors(&self, style: bool) {
+	let mut current = self.parent();
+	while let Some(node) = current {
+	    let mut dirty = node.dirty.get();
+	    dirty.descendant_style |= style;
+	    dirty.descendant_layout = true;
+	    node.dirty.set(dirty);
+	    current = node.parent();
+	}
+    }
+}
+// what the user is doing (for :hover, :active, :focus and :visited)
+impl Node {
+    // moves :hover to an element and its ancestors (or takes it away), returning whether anything changed
+    pub fn set_hovered(&self, element: Option<&Rc<Node>>) -> bool {
+	self.move_state(element, true, |state| &mut state.hover)
+    }
+    // moves :active to an element and its ancestors
+    pub fn set_active(&self, element: Option<&Rc<Node>>) -> bool {
+	self.move_state(element, true, |state| &mut state.active)
+    }
+    // moves :focus to an element
+    pub fn set_focused(&self, element: Option<&Rc<Node>>) -> bool {
+	self.move_state(element, false, |state| &mut state.focus)
+    }
+    // sets whether this is a link to a visited page
+    pub fn set_visited(&self, visited: bool) -> bool {
+	self.update_state(|state| state.visited = visited)
+    }
+    // gives a state to only an element (and its ancestors if they share it) out of all the elements in this document
+    fn move_state(&self, element: Option<&Rc<Node>>, ancestors: bool, flag: fn(&mut ElementState) -> &mut bool) -> bool {
+	let mut elements = Vec::new();
+	let mut current = element.cloned();
+	while let Some(node) = current {
+	    if !node.is_element() {
+		break;
+	    }
+	    current = if ancestors { node.parent() } else { None };
+	    elements.push(node);
+	}
+	let mut changed = false;
+	for node in self.descendant_elements() {
+	    let has_state = elements.iter().any(|e| Rc::ptr_eq(e, &node));
+	    changed |= node.update_state(|state| *flag(state) = has_state);
+	}
+	changed
+    }
+    // changes the state of this element, restyling what it might affect if it changed
+    fn update_state(&self, change: impl FnOnce(&mut ElementState)) -> bool {
+	let old_state = self.state.get();
+	let mut state = old_state;
+	change(&mut state);
+	if state == old_state {
+	    return false;
+	}
+	self.state.set(state);
+	// selectors like :hover + p affect siblings too
+	match self.parent() {
+	    Some(parent) => parent.invalidate_style(),
+	    None => self.invalidate_style(),
+	}
+	true
+    }
+}
+// serializing back to html
+impl Node {
+    // html of this node and everything inside of it
+    pub fn outer_html(&self) -> String {
+	match &self.node_type {
+	    NodeType::Text(text) => {
+		// text inside of <script>, <style>, etc. isn't escaped
+		let raw = match self.parent() {
+		    Some(parent) => parent.namespace() == Some(Namespace::Html) && parent.tag_name().is_some_and(|t| rules::RAW_TEXT_ELEMENTS.contains(&t)),
+		    None => false,
+		};
+		if raw {
+		    text.borrow().to_string()
+		} else {
+		    escape_html(&text.borrow(), false)
+		}
+	    },
+	    NodeType::Container(tag_name, _, params, _) => {
+		let mut html = format!("<{}", tag_name);
+		for (name, value) in params.borrow().iter() {
+		    html += &format!(" {}=\"{}\"", name, escape_html(value, true));
+		}
+		html += ">";
+		// empty elements don't have an end tag
+		if self.is_empty_element() {
+		    return html;
+		}
+		html += &self.inner_html();
+		html += &format!("</{}>", tag_name);
+		html
+	    },
+	    NodeType::Document(_) => self.inner_html(),
+	    NodeType::Comment(text) => format!("<!--{}-->", text.borrow()),
+	    NodeType::Doctype(name) => format!("<!DOCTYPE {}>", name),
+	}
+    }
+    // html of everything inside of this node
+    pub fn inner_html(&self) -> String {
+	let mut html = String::new();
+	match &self.node_type {
+	    NodeType::Text(_) | NodeType::Comment(_) | NodeType::Doctype(_) => return html,
+	    NodeType::Container(tag_name, children, _, namespace) => {
+		// the parser drops a newline right after these tags, so add one to keep it
+		if namespace == &Namespace::Html && ["pre", "listing", "textarea"].contains(&tag_name.as_str()) {
+		    if let Some(NodeType::Text(text)) = children.borrow().first().map(|c| &c.node_type) {
+			if text.borrow().starts_with('\n') {
+			    html.push('\n');
+			}
+		    }
+		}
+	    },
+	    NodeType::Document(_) => {},
+	}
+	for child in self.children().borrow().iter() {
+	    html += &child.outer_html();
+	}
+	html
+    }
+}
+// finds the value of an attribute in a list of them
+fn find_attribute<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a String> {
+    params.iter().find(|(n, _)| n == name).map(|(_, value)| value)
+}
+// escapes text or attribute values for html
+fn escape_html(text: &str, attribute: bool) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+	match c {
+	    '&' => escaped += "&amp;",
+	    '\u{a0}' => escaped += "&nbsp;",
+	    '"' if attribute => escaped += "&quot;",
+	    '<' if !attribute => escaped += "&lt;",
+	    '>' if !attribute => escaped += "&gt;",
+	    _ => escaped.push(c),
+	}
+    }
+    escaped
 }
 // print tree for debugging
 impl std::fmt::Display for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match &self.node_type {
-	    NodeType::Text(s) => write!(f, "{}\n", s),
-	    NodeType::Container(tag_name, children, params) => {
+	    NodeType::Text(s) => write!(f, "{}\n", s.borrow()),
+	    NodeType::Comment(s) => writeln!(f, "<!--{}-->", s.borrow()),
+	    NodeType::Doctype(name) => writeln!(f, "<!DOCTYPE {}>", name),
+	    NodeType::Container(tag_name, children, params, _) => {
+		let params = params.borrow();
 		let mut printed = format!("{}", tag_name);
 		if params.len() > 0 {
 		    printed += "(";
-		    for (param, value) in params {
+		    for (param, value) in params.iter() {
 			printed += &format!("{}=\"{}\",", param, value);
 		    }
 		    printed.pop();
@@ -152,8 +755,8 @@ impl std::fmt::Display for Node {
 		}
 		if self.css.borrow().len() > 0 {
 		    printed += "{";
-		    for (key, value) in &*self.css.borrow() {
-			printed += &format!("{}:{},", key, value);
+		    for (key, declaration) in &*self.css.borrow() {
+			printed += &format!("{}:{},", key, css::serialize(&declaration.value));
 		    }
 		    printed.pop();
 		    printed += "}";
@@ -184,222 +787,601 @@ impl std::fmt::Display for Node {
     }
 }
 
-pub fn parse(html: String) -> Rc<Node> {
-    // document to return
-    let document = Rc::new(Node::get_document());
-    // vec containing containers parser is currently in
-    let mut current_containers = Vec::new();
-    // whether parser is currently inside of tag
-    let mut in_tag = false;
-    // whether this tag is an open tag
-    let mut tag_open = true;
-    // text content inside of tag
-    let mut tag_content = String::from("");
-    // text content inside of containers
-    let mut text_content = String::from("");
-    // whether parser is in comment
-    let mut in_comment = false;
-    // stuff inside of comment (to detect end)
-    let mut comment_content = String::from("");
-    // whether parser is in doctype declaration
-    let mut in_doctype = false;
-    // special string stuff
-    let mut in_str = false;
-    let mut str_char = '\"';
-    let mut str_ignore_next = false;
-    // goes through all characters
-    for c in html.chars() {
-	if in_tag {
-	    if tag_open {
-		if in_str {
-		    if c == str_char && !str_ignore_next {
-			in_str = false;
-		    }
-		    str_ignore_next = c == '\\' && !str_ignore_next;
-		} else {
-		    if c == '\'' || c == '\"' {
-			in_str = true;
-			str_char = c.clone();
+// builds a document from html that can arrive a piece at a time
+pub struct Parser {
+    tokenizer: Tokenizer,
+    // document being built
+    document: Rc<Node>,
+    // elements the parser is currently inside of
+    open_elements: Vec<Rc<Node>>,
+    // whether a newline at the start of the next text is dropped (right after <pre>)
+    skip_newline: bool,
+    // elements that were added or had text added to them since the changes were last taken
+    changed: Vec<Rc<Node>>,
+}
+impl Parser {
+    pub fn new() -> Parser {
+	Parser{tokenizer: Tokenizer::new(),
+	       document: Rc::new(Node::get_document()),
+	       open_elements: Vec::new(),
+	       skip_newline: false,
+	       changed: Vec::new()}
+    }
+    // the document so far
+    pub fn document(&self) -> Rc<Node> {
+	Rc::clone(&self.document)
+    }
+    // parses the next piece of html
+    pub fn feed(&mut self, html: &str) {
+	for c in html.chars() {
+	    self.tokenizer.feed_char(c);
+	    self.process_tokens();
+	}
+	// text that has arrived so far can be shown already
+	self.tokenizer.flush_text();
+	self.process_tokens();
+    }
+    // parses whatever is left once all the html has arrived
+    pub fn finish(&mut self) {
+	self.tokenizer.finish();
+	self.process_tokens();
+	self.document.invalidate_style();
+    }
+    // the elements that changed since this was last called, so only they have to be looked at again
+    pub fn take_changes(&mut self) -> Vec<Rc<Node>> {
+	std::mem::take(&mut self.changed)
+    }
+    // adds a node to the end of another one while parsing
+    fn insert(&mut self, parent: &Rc<Node>, node: Rc<Node>) {
+	if !parent.can_have_children() {
+	    return;
+	}
+	parent.push_parsed_child(Rc::clone(&node));
+	if node.is_element() {
+	    self.changed.push(node);
+	} else if parent.is_element() {
+	    self.changed.push(Rc::clone(parent));
+	}
+    }
+    fn process_tokens(&mut self) {
+	while let Some(token) = self.tokenizer.next_token() {
+	    self.process(token);
+	}
+	// cdata sections only exist inside of svg and mathml
+	let foreign = self.current_node().namespace().is_some_and(|namespace| namespace != Namespace::Html);
+	self.tokenizer.set_allow_cdata(foreign);
+    }
+    // node that new nodes get added to
+    fn current_node(&self) -> Rc<Node> {
+	match self.open_elements.last() {
+	    Some(node) => Rc::clone(node),
+	    None => Rc::clone(&self.document),
+	}
+    }
+    // closes open html elements up to and including the last one with a tag name
+    fn close_element(&mut self, tag_name: &str) {
+	if let Some(index) = self.open_elements.iter().rposition(|e| e.tag_name() == Some(tag_name) && e.namespace() == Some(Namespace::Html)) {
+	    self.open_elements.truncate(index);
+	}
+    }
+    // whether a token is handled by the rules for svg and mathml instead of the ones for html
+    fn in_foreign_content(&self, start_tag: Option<&str>) -> bool {
+	let node = match self.open_elements.last() {
+	    Some(node) => node,
+	    None => return false,
+	};
+	if node.namespace() == Some(Namespace::Html) {
+	    return false;
+	}
+	match start_tag {
+	    // some foreign elements can have html start tags inside of them
+	    Some(tag_name) => {
+		let text_integration_point = node.is_mathml_text_integration_point() && tag_name != "mglyph" && tag_name != "malignmark";
+		let svg_in_annotation = node.tag_name() == Some("annotation-xml") && tag_name == "svg";
+		!text_integration_point && !svg_in_annotation && !node.is_html_integration_point()
+	    },
+	    None => true,
+	}
+    }
+    // closes svg and mathml elements until back somewhere html start tags can go
+    fn close_foreign_elements(&mut self) {
+	while self.in_foreign_content(Some("")) {
+	    self.open_elements.pop();
+	}
+    }
+    // adds an svg or mathml element
+    fn insert_foreign_element(&mut self, tag: Tag, namespace: Namespace) {
+	// names are lowercased by the tokenizer, but some svg and mathml names have capitals
+	let (tag_name, attribute_names): (String, &[(&str, &str)]) = match namespace {
+	    Namespace::Svg => {
+		let tag_name = rules::SVG_TAG_NAMES.iter().find(|(lower, _)| lower == &tag.name).map_or(tag.name.as_str(), |(_, name)| name);
+		(tag_name.to_string(), &rules::SVG_ATTRIBUTE_NAMES)
+	    },
+	    _ => (tag.name.clone(), &[("definitionurl", "definitionURL")]),
+	};
+	let attributes = tag.attributes.into_iter().map(|(name, value)| {
+	    match attribute_names.iter().find(|(lower, _)| lower == &name) {
+		Some((_, name)) => (name.to_string(), value),
+		None => (name, value),
+	    }
+	}).collect();
+	let node = Rc::new(Node::from_tag(tag_name, attributes, namespace));
+	self.insert(&self.current_node(), Rc::clone(&node));
+	// unlike in html, <path/> has no children
+	if !tag.self_closing {
+	    self.open_elements.push(node);
+	}
+    }
+    // closes an open element if it is found before any of the boundary elements
+    fn close_element_before(&mut self, tag_names: &[&str], boundaries: &[&str]) {
+	for element in self.open_elements.iter().rev() {
+	    let tag_name = element.tag_name().unwrap_or("");
+	    if tag_names.contains(&tag_name) {
+		let tag_name = tag_name.to_string();
+		self.close_element(&tag_name);
+		return;
+	    }
+	    if boundaries.contains(&tag_name) {
+		return;
+	    }
+	}
+    }
+    fn process(&mut self, token: Token) {
+	let skip_newline = self.skip_newline;
+	self.skip_newline = false;
+	match token {
+	    Token::StartTag(tag) => {
+		if self.in_foreign_content(Some(&tag.name)) {
+		    // html elements can't go inside of svg or mathml, so they end it
+		    let breaks_out = rules::BREAKS_OUT_OF_FOREIGN_ELEMENTS.contains(&tag.name.as_str())
+			|| (tag.name == "font" && tag.attributes.iter().any(|(name, _)| ["color", "face", "size"].contains(&name.as_str())));
+		    if !breaks_out {
+			let namespace = self.current_node().namespace().unwrap_or(Namespace::Html);
+			self.insert_foreign_element(tag, namespace);
+			return;
 		    }
+		    self.close_foreign_elements();
 		}
-		if c == '/' && tag_content == "".to_string() {
-		    tag_open = false;
-		} else if tag_content == "!--" && !in_str {
-		    in_tag = false;
-		    in_comment = true;
-		    tag_content = "".to_string();
-		} else if tag_content.to_uppercase() == "!DOCTYPE" && !in_str {
-		    in_doctype = true;
-		    tag_content = "".to_string();
-		} else if c == '>' && !in_str {
-		    in_tag = false;
-		    if in_doctype {
-			in_doctype = false;
-		    } else {
-			// adds new container node
-			let node = Rc::new(Node::from_tag(tag_content.clone()));
-			if !node.is_empty_element() {
-			    current_containers.push(node);
-			} else {
-			    if current_containers.len() > 0 {
-				let index = current_containers.len()-1;
-				current_containers[index].children().borrow_mut().push(Rc::clone(&node));
-				*node.parent.borrow_mut() = Some(Rc::clone(&current_containers[index])).to_owned();
-			    } else {
-				document.children().borrow_mut().push(Rc::clone(&node));
+		match tag.name.as_str() {
+		    "svg" => return self.insert_foreign_element(tag, Namespace::Svg),
+		    "math" => return self.insert_foreign_element(tag, Namespace::MathMl),
+		    _ => {},
+		}
+		// some tags end elements that are still open
+		if rules::CLOSES_P_ELEMENTS.contains(&tag.name.as_str()) {
+		    self.close_element_before(&["p"], &["button", "table", "td", "th", "html"]);
+		}
+		match tag.name.as_str() {
+		    "li" => self.close_element_before(&["li"], &["ul", "ol", "menu", "table"]),
+		    "dt" | "dd" => self.close_element_before(&["dt", "dd"], &["dl", "table"]),
+		    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+			if let Some(tag_name) = self.current_node().tag_name() {
+			    if ["h1", "h2", "h3", "h4", "h5", "h6"].contains(&tag_name) {
+				self.open_elements.pop();
 			    }
 			}
-		    }
-		    tag_content = "".to_string();
-		} else if !(in_str && c == str_char && !str_ignore_next) && !(!in_str && (c == '\'' || c == '\"')){
-		    tag_content.push(c);
+		    },
+		    _ => {},
+		}
+		// adds new container node
+		let node = Rc::new(Node::from_tag(tag.name.clone(), tag.attributes.into_iter().collect(), Namespace::Html));
+		self.insert(&self.current_node(), Rc::clone(&node));
+		if !node.is_empty_element() {
+		    self.open_elements.push(node);
 		}
-	    } else {
-		if c == '>' {
-		    in_tag = false;
-		    // checks if there are multiple containers
-		    if current_containers.len() > 1 {
-			// removes node but adds as child to parent node
-			let index = current_containers.len()-2;
-			let node = current_containers.remove(current_containers.len()-1);
-			current_containers[index].children().borrow_mut().push(Rc::clone(&node));
-			*node.parent.borrow_mut() = Some(Rc::clone(&current_containers[index])).to_owned();
+		// text inside of some elements isn't html
+		let tag_name = tag.name.as_str();
+		if tag_name == "plaintext" {
+		    self.tokenizer.set_state(State::PlainText);
+		} else if rules::RAW_TEXT_ELEMENTS.contains(&tag_name) {
+		    self.tokenizer.set_state(State::RawText);
+		} else if rules::RCDATA_ELEMENTS.contains(&tag_name) {
+		    self.tokenizer.set_state(State::RcData);
+		}
+		self.skip_newline = ["pre", "listing", "textarea"].contains(&tag_name);
+	    },
+	    Token::EndTag(tag) => {
+		if self.in_foreign_content(None) {
+		    // </br> and </p> end svg and mathml like their start tags do
+		    if tag.name == "br" || tag.name == "p" {
+			self.close_foreign_elements();
 		    } else {
-			document.children().borrow_mut().push(Rc::clone(&current_containers.remove(0)));
+			// closes the matching foreign element, unless an html element is found first
+			for (index, element) in self.open_elements.iter().enumerate().rev() {
+			    if element.tag_name().is_some_and(|name| name.eq_ignore_ascii_case(&tag.name)) {
+				self.open_elements.truncate(index);
+				return;
+			    }
+			    if element.namespace() == Some(Namespace::Html) {
+				break;
+			    }
+			}
 		    }
-		    tag_content = "".to_string();
+		}
+		// </br> is treated like <br>
+		if tag.name == "br" {
+		    self.insert(&self.current_node(), Node::create_element("br"));
 		} else {
-		    tag_content.push(c);
+		    self.close_element(&tag.name);
 		}
-	    }
-	} else {
-	    if in_comment {
-		comment_content.push(c);
-		if comment_content.ends_with("-->") {
-		    in_comment = false;
-		    comment_content = "".to_string();
+	    },
+	    Token::Text(mut text) => {
+		// a newline directly after <pre>, <listing> or <textarea> is ignored
+		if skip_newline && text.starts_with('\n') {
+		    text.remove(0);
 		}
-	    } else {
-		if c == '<' {
-		    in_tag = true;
-		    tag_open = true;
-		    if text_content.trim() != "".to_string() {
-			// adds new text node
-			let node = Rc::new(Node::from_text(text_content));
-			let index = current_containers.len()-1;
-			current_containers[index].children().borrow_mut().push(Rc::clone(&node));
-			*node.parent.borrow_mut() = Some(Rc::clone(&current_containers[index])).to_owned();
-
+		let parent = self.current_node();
+		// whitespace outside of any element doesn't matter
+		if text.is_empty() || (self.open_elements.is_empty() && text.trim().is_empty()) {
+		    return;
+		}
+		// text that continues the last text node is added to it
+		let last_child = parent.children().borrow().last().map(Rc::clone);
+		if let Some(last_child) = last_child {
+		    if let NodeType::Text(last_text) = &last_child.node_type {
+			last_text.borrow_mut().push_str(&text);
+			last_child.invalidate_layout();
+			if parent.is_element() {
+			    self.changed.push(Rc::clone(&parent));
+			}
+			return;
 		    }
-		    text_content = "".to_string();
-		} else {
-		    text_content.push(c);
 		}
-	    }
+		// adds new text node
+		self.insert(&parent, Rc::new(Node::from_text(text)));
+	    },
+	    Token::Comment(text) => {
+		self.insert(&self.current_node(), Node::create_comment(&text));
+	    },
+	    // a doctype only counts before the document starts
+	    Token::Doctype(name) => {
+		if self.open_elements.is_empty() {
+		    self.insert(&Rc::clone(&self.document), Rc::new(Node::from_type(NodeType::Doctype(name))));
+		}
+	    },
 	}
     }
-    return document;
 }
 
-// check wheter css selector applies
-fn selector_applies(node: Rc<Node>, selector: String) -> bool {
-    let mut descendants = selector.split(" ").collect::<Vec<&str>>();
-    let mut applies = true;
-    if node.basic_selector_applies(descendants[descendants.len()-1].to_string()) {
-	descendants.pop();
-	descendants.reverse();
-    } else {
-	return false;
+impl Default for Parser {
+    fn default() -> Parser {
+	Parser::new()
     }
-    let mut current_node = Rc::clone(&node);
-    for basic_selector in descendants {
-	let mut basic_applies = false;
-	loop {
-	    if current_node.basic_selector_applies(basic_selector.to_string()) {
-		basic_applies = true;
-		break;
-	    }
-	    match &*Rc::clone(&current_node).parent.borrow() {
-		None => break,
-		Some(n) => current_node = Rc::clone(&n),
-	    }
-	}
-	if !basic_applies {
-	    applies = false;
-	    break;
-	}
-    }
-    return applies;
 }
 
+pub fn parse(html: String) -> Rc<Node> {
+    let mut parser = Parser::new();
+    parser.feed(&html);
+    parser.finish();
+    parser.document()
+}
 
-// apply css to all nodes
-pub fn apply_css(css_rules: Vec<(String, HashMap<String, String>)>, node: Rc<Node>) {
-    match &node.node_type {
-	NodeType::Document(children) => {
-	    // get to all the other nodes in tree
-	    for child in children.borrow().iter() {
-		apply_css(css_rules.clone(), Rc::clone(child));
-	    }
-	},
-	NodeType::Container(tag_name, children, params) => {
-	    // applies default rules
-	    let default = rules::DEFAULT_CSS.iter().find(|t| t.0 == tag_name);
-	    match default {
-		Some((_, default)) => {
-		    let rules = default.split(";");
-		    for rule in rules {
-			let parts = rule.split(":").collect::<Vec<&str>>();
-			if parts.len() == 2 {
-			    let key = parts[0].trim().to_string();
-			    let value = parts[1].trim().to_string();
-			    apply_css_rule(Rc::clone(&node), key, value);
-			}
-		    }
-		},
-		None => {},
-	    }
-	    // applies rules
-	    for (selector, rules) in css_rules.clone() {
-		if selector_applies(Rc::clone(&node), selector) {
-		    for (key, value) in rules {
-			apply_css_rule(Rc::clone(&node), key, value);
-		    }
+// check whether a css selector applies, starting with the element itself and going outwards
+// (scope is the element relative selectors, like the ones in :has(), start from)
+fn selector_applies(node: &Node, selector: &Selector, scope: Option<&Node>) -> bool {
+    if !selector.compound.iter().all(|simple| node.simple_selector_applies(simple, scope)) {
+	return false;
+    }
+    match &selector.context {
+	None => true,
+	Some((Combinator::Child, context)) => node.parent().is_some_and(|parent| selector_applies(&parent, context, scope)),
+	Some((Combinator::Descendant, context)) => {
+	    let mut ancestor = node.parent();
+	    while let Some(n) = ancestor {
+		if selector_applies(&n, context, scope) {
+		    return true;
 		}
+		ancestor = n.parent();
 	    }
-	    // applies inline rules
-	    match params.get("style") {
-		None => {},
-		Some(s) => {
-		    let rules = s.split(";");
-		    for rule in rules {
-			let parts = rule.split(":").collect::<Vec<&str>>();
-			if parts.len() == 2 {
-			    let key = parts[0].trim().to_string();
-			    let value = parts[1].trim().to_string();
-			    apply_css_rule(Rc::clone(&node), key, value);
-			}
-		    }
+	    false
+	},
+	Some((Combinator::NextSibling, context)) => node.previous_element_sibling().is_some_and(|sibling| selector_applies(&sibling, context, scope)),
+	Some((Combinator::SubsequentSibling, context)) => {
+	    let mut sibling = node.previous_element_sibling();
+	    while let Some(n) = sibling {
+		if selector_applies(&n, context, scope) {
+		    return true;
 		}
+		sibling = n.previous_element_sibling();
 	    }
-	    // get to all the other nodes in tree
+	    false
+	},
+    }
+}
+
+// compares an attribute's value for an attribute selector like [a^=b]
+fn attribute_value_applies(actual: &str, operator: AttributeOperator, expected: &str, ignore_case: bool) -> bool {
+    let (actual, expected) = if ignore_case {
+	(actual.to_ascii_lowercase(), expected.to_ascii_lowercase())
+    } else {
+	(actual.to_string(), expected.to_string())
+    };
+    match operator {
+	AttributeOperator::Equals => actual == expected,
+	AttributeOperator::Includes => !expected.is_empty() && !expected.contains(char::is_whitespace) && actual.split_whitespace().any(|word| word == expected),
+	AttributeOperator::DashMatch => actual == expected || actual.starts_with(&format!("{}-", expected)),
+	// an empty value never matches these
+	AttributeOperator::Prefix => !expected.is_empty() && actual.starts_with(&expected),
+	AttributeOperator::Suffix => !expected.is_empty() && actual.ends_with(&expected),
+	AttributeOperator::Substring => !expected.is_empty() && actual.contains(&expected),
+    }
+}
+
+// check whether any selector in a list applies
+fn selector_list_applies(node: &Node, selectors: &[Selector], scope: Option<&Node>) -> bool {
+    selectors.iter().any(|selector| selector_applies(node, selector, scope))
+}
+
+// whether a position (counting from 1) is an+b for some n that isn't negative
+fn nth_applies(a: i32, b: i32, position: usize) -> bool {
+    let offset = position as i32 - b;
+    if a == 0 {
+	offset == 0
+    } else {
+	offset % a == 0 && offset / a >= 0
+    }
+}
+
+// the specificity of the most specific selector in a list that applies (None if none of them do)
+fn matching_specificity(node: &Node, selectors: &[Selector]) -> Option<Specificity> {
+    selectors.iter().filter(|selector| selector_applies(node, selector, None)).map(|selector| selector.specificity()).max()
+}
+
+// apply css to all nodes that need it
+pub fn apply_css(css_rules: &[&StyleRule], node: &Node, device: &Device) {
+    let mut dirty = node.dirty.get();
+    // nothing changed at or below this node
+    if !dirty.style && !dirty.descendant_style {
+	return;
+    }
+    if dirty.style {
+	// elements get properties from the cascade, and text only inherits
+	*node.css.borrow_mut() = match &node.node_type {
+	    NodeType::Container(..) => cascaded_css(css_rules, node),
+	    _ => HashMap::new(),
+	};
+	// the root element doesn't inherit from anything
+	let parent_style = node.parent().filter(|parent| parent.is_element()).map(|parent| parent.style.borrow().clone());
+	*node.style.borrow_mut() = ComputedStyle::compute(&node.css.borrow(), parent_style.as_ref(), device);
+	// children inherit from this node, so they need to be recalculated too
+	if let NodeType::Document(children) | NodeType::Container(_, children, _, _) = &node.node_type {
 	    for child in children.borrow().iter() {
-		apply_css(css_rules.clone(), Rc::clone(child));
+		let mut child_dirty = child.dirty.get();
+		child_dirty.style = true;
+		child.dirty.set(child_dirty);
 	    }
-	},
-	_ => {}
+	}
+	dirty.layout = true;
+    }
+    // get to all the other nodes in tree
+    if let NodeType::Document(children) | NodeType::Container(_, children, _, _) = &node.node_type {
+	for child in children.borrow().iter() {
+	    apply_css(css_rules, child, device);
+	}
     }
+    dirty.style = false;
+    dirty.descendant_style = false;
+    node.dirty.set(dirty);
 }
-fn apply_css_rule(node: Rc<Node>, key: String, value: String) {
-    match &node.node_type {
-	NodeType::Container(_, children, _) => {
-	    if rules::INHERITED_PROPERTIES.iter().any(|e| e==&key) {
-		for child in children.borrow_mut().iter() {
-		    apply_css_rule(Rc::clone(child), key.clone(), value.clone());
-		}
+// picks the properties of an element from the stylesheets and style attribute
+fn cascaded_css(css_rules: &[&StyleRule], node: &Node) -> HashMap<String, Declaration> {
+    let inline = node.get_attribute("style").map(|style| css::parse_declaration_list(&style)).unwrap_or_default();
+    let mut declarations = Vec::new();
+    for rule in css_rules {
+	if let Some(specificity) = matching_specificity(node, &rule.selectors) {
+	    for declaration in rule.declarations.iter() {
+		declarations.push(CascadedDeclaration{declaration, origin: rule.origin, inline: false, specificity});
 	    }
-	},
-	_ => {},
+	}
+    }
+    for declaration in inline.iter() {
+	declarations.push(CascadedDeclaration{declaration, origin: Origin::Author, inline: true, specificity: Specificity::default()});
+    }
+    cascade(declarations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the ids of the elements in some html that selectors match
+    fn matching_ids(html: &str, selectors: &str) -> Vec<String> {
+	parse(html.to_string()).query_selector_all(selectors).iter().filter_map(|e| e.get_attribute("id")).collect()
+    }
+
+    #[test]
+    fn insert_before_puts_the_child_before_the_reference() {
+	let parent = Node::create_element("ul");
+	let (first, second) = (Node::create_element("li"), Node::create_element("li"));
+	assert!(parent.append_child(Rc::clone(&second)));
+	assert!(parent.insert_before(Rc::clone(&first), Some(&second)));
+	let children = parent.children().borrow();
+	assert!(Rc::ptr_eq(&children[0], &first) && Rc::ptr_eq(&children[1], &second));
+	assert!(Rc::ptr_eq(&first.parent().unwrap(), &parent));
+    }
+
+    #[test]
+    fn text_nodes_cant_have_children() {
+	let text = Node::create_text_node("text");
+	assert!(!text.append_child(Node::create_element("b")));
+    }
+
+    #[test]
+    fn nodes_cant_go_inside_of_themselves() {
+	let outer = Node::create_element("div");
+	let inner = Node::create_element("div");
+	outer.append_child(Rc::clone(&inner));
+	assert!(!outer.append_child(Rc::clone(&outer)));
+	assert!(!inner.append_child(Rc::clone(&outer)));
+	assert!(outer.parent().is_none());
+	assert_eq!(inner.children().borrow().len(), 0);
+    }
+
+    #[test]
+    fn moving_a_node_takes_it_out_of_its_old_parent() {
+	let (old_parent, new_parent) = (Node::create_element("div"), Node::create_element("div"));
+	let child = Node::create_element("p");
+	old_parent.append_child(Rc::clone(&child));
+	new_parent.append_child(Rc::clone(&child));
+	assert_eq!(old_parent.children().borrow().len(), 0);
+	assert!(Rc::ptr_eq(&child.parent().unwrap(), &new_parent));
+    }
+
+    #[test]
+    fn removing_a_child_invalidates_style() {
+	let parent = Node::create_element("div");
+	let child = Node::create_element("p");
+	parent.append_child(Rc::clone(&child));
+	parent.dirty.set(Dirty::default());
+	assert!(parent.remove_child(&child));
+	assert!(parent.dirty.get().style);
+	assert!(child.parent().is_none());
+	assert!(!parent.remove_child(&child));
+    }
+
+    #[test]
+    fn serializing_keeps_attribute_order_comments_and_doctype() {
+	let html = "<!DOCTYPE html><html><head><title>a &amp; b</title></head><body><!-- note --><p id=\"x\" class=\"y\" data-a=\"&quot;\">hi</p></body></html>";
+	let document = parse(html.to_string());
+	assert_eq!(document.outer_html(), html);
+	assert_eq!(parse(document.outer_html()).outer_html(), html);
+    }
+
+    #[test]
+    fn set_attribute_keeps_the_attribute_in_place() {
+	let document = parse(String::from("<p b=\"1\" a=\"2\"></p>"));
+	let p = document.query_selector("p").unwrap();
+	p.set_attribute("b", "3");
+	p.set_attribute("c", "4");
+	assert_eq!(p.outer_html(), "<p b=\"3\" a=\"2\" c=\"4\"></p>");
+	p.remove_attribute("b");
+	assert_eq!(p.outer_html(), "<p a=\"2\" c=\"4\"></p>");
+    }
+
+    #[test]
+    fn comments_dont_count_for_empty_or_text_content() {
+	let document = parse(String::from("<div><!-- x --></div><p>a<!-- b -->c</p>"));
+	assert!(document.query_selector("div:empty").is_some());
+	assert_eq!(document.query_selector("p").unwrap().text_content(), "ac");
+    }
+
+    #[test]
+    fn parser_only_reports_what_changed() {
+	let mut parser = Parser::new();
+	parser.feed("<html><body><p>a");
+	let changed: Vec<String> = parser.take_changes().iter().map(|e| e.tag_name().unwrap().to_string()).collect();
+	assert_eq!(changed, ["html", "body", "p", "p"]);
+	parser.feed("b</p><div>");
+	let changed: Vec<String> = parser.take_changes().iter().map(|e| e.tag_name().unwrap().to_string()).collect();
+	assert_eq!(changed, ["p", "div"]);
+	// earlier elements aren't restyled when new ones arrive
+	let p = parser.document().query_selector("p").unwrap();
+	p.dirty.set(Dirty::default());
+	parser.feed("<span>");
+	assert!(!p.dirty.get().style);
+    }
+
+    #[test]
+    fn combinators_match_from_the_right() {
+	let html = "<div id=d><ul id=u><li id=l1><p id=p1></p></li><li id=l2></li><li id=l3></li></ul><p id=p2></p></div>";
+	assert_eq!(matching_ids(html, "div p"), ["p1", "p2"]);
+	assert_eq!(matching_ids(html, "div > p"), ["p2"]);
+	assert_eq!(matching_ids(html, "li + li"), ["l2", "l3"]);
+	assert_eq!(matching_ids(html, "#l1 ~ li"), ["l2", "l3"]);
+	assert_eq!(matching_ids(html, "ul + p, ul > li > p"), ["p1", "p2"]);
+	// descendant combinators have to try every ancestor, not just the closest match
+	assert_eq!(matching_ids(html, "div > ul li p"), ["p1"]);
+	assert!(matching_ids(html, "li > li").is_empty());
+    }
+
+    #[test]
+    fn class_lists() {
+	let html = "<p id=a class='btn  primary'></p><p id=b class='btn-primary'></p><p id=c class=''></p>";
+	assert_eq!(matching_ids(html, ".btn"), ["a"]);
+	assert_eq!(matching_ids(html, ".primary.btn"), ["a"]);
+	assert_eq!(matching_ids(html, ".btn-primary"), ["b"]);
+	assert!(matching_ids(html, ".btn.secondary").is_empty());
+    }
+
+    #[test]
+    fn attribute_selectors_match() {
+	let html = "<p id=a data-lang=en-US title='one two'></p><p id=b data-lang=EN data-x=abc></p><p id=c data-x></p>";
+	assert_eq!(matching_ids(html, "[data-x]"), ["b", "c"]);
+	assert!(matching_ids(html, "[data-lang=en]").is_empty());
+	assert_eq!(matching_ids(html, "[data-lang=en i]"), ["b"]);
+	assert_eq!(matching_ids(html, "[data-lang|=en]"), ["a"]);
+	assert_eq!(matching_ids(html, "[data-lang|=en i]"), ["a", "b"]);
+	assert_eq!(matching_ids(html, "[title~=two]"), ["a"]);
+	assert!(matching_ids(html, "[title~='one two']").is_empty());
+	assert_eq!(matching_ids(html, "[data-x^=ab]"), ["b"]);
+	assert_eq!(matching_ids(html, "[data-x$=bc]"), ["b"]);
+	assert_eq!(matching_ids(html, "[data-x*=b]"), ["b"]);
+	// an empty value never matches ^=, $= or *=
+	assert!(matching_ids(html, "[data-x^='']").is_empty());
+    }
+
+    #[test]
+    fn structural_pseudo_classes() {
+	let html = "<ul id=u><li id=a></li><li id=b></li><p id=c></p><li id=d> </li><li id=e><!-- x --></li></ul>";
+	assert_eq!(matching_ids(html, "li:first-child"), ["a"]);
+	assert_eq!(matching_ids(html, "li:last-child"), ["e"]);
+	assert_eq!(matching_ids(html, "li:nth-child(2n+1)"), ["a", "e"]);
+	assert_eq!(matching_ids(html, "li:nth-last-child(-n+2)"), ["d", "e"]);
+	assert_eq!(matching_ids(html, "li:nth-of-type(3)"), ["d"]);
+	assert_eq!(matching_ids(html, ":only-of-type"), ["u", "c"]);
+	// whitespace counts as content, but comments don't
+	assert_eq!(matching_ids(html, "li:empty"), ["a", "b", "e"]);
+	assert_eq!(matching_ids(html, "ul :not(li, #a)"), ["c"]);
+	assert_eq!(matching_ids(html, ":is(p, #e):where(*)"), ["c", "e"]);
+	assert_eq!(matching_ids(html, "ul:has(> p)"), ["u"]);
+	assert_eq!(matching_ids(html, "li:has(+ p)"), ["b"]);
+	assert_eq!(matching_ids(html, "li:has(~ p)"), ["a", "b"]);
+	assert_eq!(parse(format!("<html>{}</html>", html)).query_selector(":root").unwrap().tag_name(), Some("html"));
+    }
+
+    #[test]
+    fn hover_applies_to_ancestors_and_restyles() {
+	let document = parse("<html><body><div><a href=x>link</a></div><p></p></body></html>".to_string());
+	let link = document.query_selector("a").unwrap();
+	apply_css(&[], &document, &Device::default());
+	assert!(document.set_hovered(Some(&link)));
+	let hovered: Vec<String> = document.query_selector_all(":hover").iter().map(|e| e.tag_name().unwrap().to_string()).collect();
+	assert_eq!(hovered, ["html", "body", "div", "a"]);
+	// the parent is restyled, since selectors like :hover + p can change siblings too
+	assert!(link.parent().unwrap().dirty.get().style);
+	// nothing changes when it's already there
+	assert!(!document.set_hovered(Some(&link)));
+	assert!(document.set_hovered(None));
+	assert!(document.query_selector(":hover").is_none());
+    }
+
+    #[test]
+    fn visited_links() {
+	let document = parse("<a id=a href=x></a><a id=b href=y></a><a id=c></a>".to_string());
+	document.query_selector("#b").unwrap().set_visited(true);
+	let ids = |selector: &str| document.query_selector_all(selector).iter().filter_map(|e| e.get_attribute("id")).collect::<Vec<String>>();
+	assert_eq!(ids(":link"), ["a"]);
+	assert_eq!(ids(":visited"), ["b"]);
+    }
+
+    // the value of a property on an element after applying css to a document
+    fn cascaded_value(html: &str, css: &str, selector: &str, property: &str) -> Option<String> {
+	let document = parse(html.to_string());
+	let stylesheet = css::parse(css.to_string(), Origin::Author);
+	apply_css(&stylesheet.rules.iter().collect::<Vec<_>>(), &document, &Device::default());
+	let element = document.query_selector(selector).unwrap();
+	let value = element.css.borrow().get(property).map(|declaration| css::serialize(&declaration.value));
+	value
+    }
+
+    #[test]
+    fn cascade_uses_specificity_importance_and_order() {
+	let html = "<p id=a class=b style='color: green'>x</p>";
+	assert_eq!(cascaded_value(html, "#a { margin-top: 1px } .b.b { margin-top: 2px }", "p", "margin-top").as_deref(), Some("1px"));
+	assert_eq!(cascaded_value(html, "p { margin-top: 1px } p { margin-top: 2px }", "p", "margin-top").as_deref(), Some("2px"));
+	assert_eq!(cascaded_value(html, "#a { color: red }", "p", "color").as_deref(), Some("green"));
+	assert_eq!(cascaded_value(html, "p { color: red !important }", "p", "color").as_deref(), Some("red"));
+	// !important isn't part of the value
+	assert_eq!(cascaded_value(html, "p { margin-top: 1px!important } #a { margin-top: 2px }", "p", "margin-top").as_deref(), Some("1px"));
     }
-    node.css.borrow_mut().insert(key, value);
 }
@@ -1,3 +1,6 @@
 pub mod html;
+pub mod tokenizer;
 pub mod css;
+pub mod style;
+pub mod font;
 pub mod render;
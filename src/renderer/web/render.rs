@@ -1,11 +1,19 @@
-use std::rc::Rc;
-use cairo::{Context, Path, FontWeight, FontSlant, Glyph};
+use cairo::{Context, Path, Glyph};
 use crate::renderer::{layout::{Distance, Content, Label, Block}, web::html::{Node, NodeType}};
+use crate::renderer::web::style::{Display, WhiteSpace};
+use crate::renderer::web::font::Fonts;
+
+use std::rc::Rc;
 
 // recursive function to draw nodes
-pub fn draw_node(cr: &Context, node: Rc<Node>, left: Distance, top: Distance, width: i32, height: i32) -> Vec<(Path, [f64;4])> {
+pub fn draw_node(cr: &Context, node: &Node, fonts: &Fonts, left: Distance, top: Distance, width: i32, height: i32) -> Vec<(Path, [f64;4])> {
+    // comments and doctypes aren't drawn
+    if let NodeType::Comment(_) | NodeType::Doctype(_) = node.node_type {
+	return Vec::new();
+    }
     // don't draw display: none
-    if node.css.borrow().get("display") == Some(&"none".to_string()) {
+    if node.style.borrow().display == Display::None {
+	node.render.borrow_mut().bounds = [0.0;4];
 	return vec![(cr.copy_path().expect("Invalid cairo surface state or path"), [1.0, 1.0, 1.0, 0.0])];
     }
     let render = &mut *node.render.borrow_mut();
@@ -27,7 +35,7 @@ pub fn draw_node(cr: &Context, node: Rc<Node>, left: Distance, top: Distance, wi
 		    },
 		    _ => {},
 		}
-		child_paths.append(&mut draw_node(cr, Rc::clone(&child), left+content.margin_left+content.padding_left, top+content.margin_top+content.padding_top+child_height, width, height));
+		child_paths.append(&mut draw_node(cr, child, fonts, left+content.margin_left+content.padding_left, top+content.margin_top+content.padding_top+child_height, width, height));
 		let child_render = &mut *child.render.borrow_mut();
 		match &child_render.height {
 		    Some(h) => {
@@ -56,6 +64,7 @@ pub fn draw_node(cr: &Context, node: Rc<Node>, left: Distance, top: Distance, wi
 	    let rect_width = get_absolute_pos(width, render.visual_width);
 	    let rect_height = get_absolute_pos(height, visual_height);
 	    cr.rectangle(start_x, start_y, rect_width, rect_height);
+	    render.bounds = [start_x, start_y, rect_width, rect_height];
 	    // return paths with current node at the bottom
 	    let mut paths = vec![(cr.copy_path().expect("Invalid cairo surface state or path"), content.color)];
 	    paths.append(&mut child_paths);
@@ -63,9 +72,19 @@ pub fn draw_node(cr: &Context, node: Rc<Node>, left: Distance, top: Distance, wi
 	},
 	// draw text
 	Content::Text(label) => {
-	    // create freetype library and face (for size info)
-	    let lib = freetype::Library::init().unwrap();
-	    let face = lib.new_face(crate::rules::DEFAULT_FONT, 0).unwrap();
+	    // use the font from @font-face that fits best, or the default font if there isn't one
+	    // (text that was only whitespace, or that there's no font for, takes up no space)
+	    let font = match fonts.find(&label.font_family, label.weight, label.style) {
+		Some(font) if !label.text.is_empty() => font,
+		_ => {
+		    render.width = Some(Distance::Absolute(0.));
+		    render.height = Some(Distance::Absolute(0.));
+		    render.bounds = [0.0;4];
+		    return vec![(cr.copy_path().expect("Invalid cairo surface state or path"), label.font_color)];
+		},
+	    };
+	    let face = &font.face;
+	    cr.set_font_face(font.cairo_face(label.weight, label.style));
 	    // set size in cargo and freetype
 	    let size = get_absolute_pos(height, label.font_size);
 	    cr.set_font_size(size);
@@ -81,7 +100,27 @@ pub fn draw_node(cr: &Context, node: Rc<Node>, left: Distance, top: Distance, wi
 	    let mut x = get_absolute_pos(width, left);
 	    let mut y = get_absolute_pos(height, top) + face_ascender + face_height;
 	    let mut prev_char: Option<char> = None;
+	    // furthest the text goes to the right
+	    let mut right = x;
+	    // width of a space, used for tab stops
+	    face.load_char(' ' as usize, freetype::face::LoadFlag::RENDER).unwrap();
+	    let space_width = face.glyph().metrics().horiAdvance as f64 / 64.0;
 	    for c in label.text.chars() {
+		// preserved newlines start a new line
+		if c == '\n' {
+		    x = get_absolute_pos(width, left);
+		    y += face_height;
+		    prev_char = None;
+		    continue;
+		}
+		// preserved tabs move to the next tab stop (every 8 spaces)
+		if c == '\t' {
+		    let tab_width = space_width * 8.;
+		    let line_x = x - get_absolute_pos(width, left);
+		    x += tab_width - line_x % tab_width;
+		    prev_char = None;
+		    continue;
+		}
 		face.load_char(c as usize, freetype::face::LoadFlag::RENDER).unwrap();
 		let ft_glyph = face.glyph();
 		let glyph_metrics = ft_glyph.metrics();
@@ -90,14 +129,18 @@ pub fn draw_node(cr: &Context, node: Rc<Node>, left: Distance, top: Distance, wi
 		if let Some(prev_c) = prev_char {
 		    x += face.get_kerning(face.get_char_index(prev_c as usize), face.get_char_index(c as usize), freetype::face::KerningMode::KerningUnfitted).unwrap().x as f64 / 64.0;
 		}
-		if x > get_absolute_pos(width, render.visual_width) {
+		if label.wrap && x > get_absolute_pos(width, render.visual_width) {
 		    x = get_absolute_pos(width, left);
 		    y += face_height;
 		}
+		right = right.max(x);
 		prev_char = Some(c);
 	    }
 	    render.width = Some(Distance::Absolute(y));
 	    render.height = Some(Distance::Absolute(y-get_absolute_pos(height, top)-face_ascender));
+	    let start_x = get_absolute_pos(width, left);
+	    let start_y = get_absolute_pos(height, top);
+	    render.bounds = [start_x, start_y, right-start_x, y-start_y-face_ascender];
 	    // return paths
 	    cr.show_glyphs(&glyphs).expect("Invalid cairo surface state or path");
 	    let color = label.font_color;
@@ -106,228 +149,316 @@ pub fn draw_node(cr: &Context, node: Rc<Node>, left: Distance, top: Distance, wi
     }
 }
 
+// finds the innermost element drawn at a point (text counts as the element it's in)
+pub fn element_at(node: &Rc<Node>, x: f64, y: f64) -> Option<Rc<Node>> {
+    if node.style.borrow().display == Display::None {
+	return None;
+    }
+    if let NodeType::Document(children) | NodeType::Container(_, children, _, _) = &node.node_type {
+	// later children are drawn on top
+	for child in children.borrow().iter().rev() {
+	    if let Some(element) = element_at(child, x, y) {
+		return Some(element);
+	    }
+	}
+    }
+    let [left, top, width, height] = node.render.borrow().bounds;
+    if x < left || y < top || x >= left+width || y >= top+height {
+	return None;
+    }
+    match &node.node_type {
+	NodeType::Container(..) => Some(Rc::clone(node)),
+	NodeType::Text(_) => node.parent(),
+	NodeType::Document(_) | NodeType::Comment(_) | NodeType::Doctype(_) => None,
+    }
+}
+
 // gets the absolute position based on screen size
 fn get_absolute_pos(size: i32, pos: Distance) -> f64 {
-    match pos {
-	Distance::Absolute(pixels) => pixels,
-	Distance::Relative(percent) => percent * size as f64,
-	Distance::Combo(pixels, percent) => pixels + percent * size as f64
+    pos.to_pixels(size as f64)
+}
+
+// whether a character is whitespace that the white-space property can collapse
+fn is_collapsible(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0c')
+}
+
+// the text nodes laid out in the same block as a node's children, in order (None where a block breaks the line)
+fn inline_texts(node: &Rc<Node>, texts: &mut Vec<Option<Rc<Node>>>) {
+    for child in node.children().borrow().iter() {
+	match child.node_type {
+	    NodeType::Text(_) => texts.push(Some(Rc::clone(child))),
+	    NodeType::Container(..) => match child.style.borrow().display {
+		Display::None => {},
+		Display::Inline => inline_texts(child, texts),
+		_ => texts.push(None),
+	    },
+	    _ => {},
+	}
+    }
+}
+
+// whether whitespace at the start and end of a text node is at the edge of its line and gets stripped
+fn whitespace_edges(node: &Node) -> (bool, bool) {
+    // find the block the text is laid out in
+    let mut block = node.parent();
+    while let Some(parent) = block.clone() {
+	if !parent.is_element() || parent.style.borrow().display != Display::Inline {
+	    break;
+	}
+	block = parent.parent();
     }
+    let mut texts = Vec::new();
+    if let Some(block) = block {
+	inline_texts(&block, &mut texts);
+    }
+    let Some(index) = texts.iter().position(|text| text.as_deref().is_some_and(|text| std::ptr::eq(text, node))) else {
+	return (true, true);
+    };
+    let text_of = |node: &Rc<Node>| match &node.node_type {
+	NodeType::Text(text) => text.borrow().clone(),
+	_ => String::new(),
+    };
+    // leading whitespace goes if nothing comes before it on the line, or if what does already ends in a collapsible space
+    let before = texts[..index].iter().rev().map_while(Option::as_ref).find(|text| !text_of(text).is_empty());
+    let strip_start = match before {
+	Some(before) => {
+	    let white_space = before.style.borrow().white_space;
+	    text_of(before).ends_with(is_collapsible) && white_space != WhiteSpace::Pre && white_space != WhiteSpace::PreWrap
+	},
+	None => true,
+    };
+    // trailing whitespace goes if nothing but whitespace comes after it on the line
+    let strip_end = texts[index+1..].iter().map_while(Option::as_ref).all(|text| text_of(text).trim_matches(is_collapsible).is_empty());
+    (strip_start, strip_end)
 }
 
-// gets color in rgba
-pub fn get_color(color: String) -> [f64;4] {
-    if color.starts_with("rgb") {
-	let args = color.trim_start_matches("rgb(").trim_end_matches(")").split(",").collect::<Vec<&str>>();
-	[args[0].trim().parse::<f64>().unwrap()/255.,
-	 args[1].trim().parse::<f64>().unwrap()/255.,
-	 args[2].trim().parse::<f64>().unwrap()/255.,
-	 1.0]
-    } else if color.starts_with("#") {
-	let hex_color = color.trim_start_matches("#");
-	let r = u32::from_str_radix(&hex_color[0..2], 16).unwrap() as f64;
-        let g = u32::from_str_radix(&hex_color[2..4], 16).unwrap() as f64;
-        let b = u32::from_str_radix(&hex_color[4..6], 16).unwrap() as f64;
-	[r/255.,
-	 g/255.,
-	 b/255.,
-	 1.0]
-    } else {
-	for (key, value) in crate::rules::DEFAULT_COLORS {
-	    if key.to_string() == color {
-		return get_color(value.to_string());
+// collapses whitespace according to the white-space property, stripping it at the start and end if asked to
+pub fn process_whitespace(text: &str, white_space: WhiteSpace, strip_start: bool, strip_end: bool) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    // pre and pre-wrap keep all whitespace
+    if white_space == WhiteSpace::Pre || white_space == WhiteSpace::PreWrap {
+	return text;
+    }
+    let keep_newlines = white_space == WhiteSpace::PreLine;
+    let mut processed = String::new();
+    // whether there is whitespace waiting to be collapsed into a single space
+    let mut pending_space = false;
+    for c in text.chars() {
+	if c == '\n' && keep_newlines {
+	    // spaces around a preserved newline are removed
+	    pending_space = false;
+	    processed.push('\n');
+	} else if is_collapsible(c) {
+	    pending_space = true;
+	} else {
+	    // spaces at the start of a line are dropped
+	    let line_start = processed.ends_with('\n') || (processed.is_empty() && strip_start);
+	    if pending_space && !line_start {
+		processed.push(' ');
 	    }
+	    pending_space = false;
+	    processed.push(c);
 	}
-	[0.0, 0.0, 0.0, 1.0]
     }
+    // and so are the ones at the end of one
+    let line_start = processed.ends_with('\n') || (processed.is_empty() && strip_start);
+    if pending_space && !strip_end && !line_start {
+	processed.push(' ');
+    }
+    processed
 }
 
-// render nodes into boxes
-pub fn render_node(node: Rc<Node>, max_width: Distance, max_height: Distance) {
+// render nodes that changed into boxes
+pub fn render_node(node: &Node, max_width: Distance, max_height: Distance) {
+    let mut dirty = node.dirty.get();
+    // nothing changed at or below this node
+    if !dirty.layout && !dirty.descendant_layout {
+	return;
+    }
     match &node.node_type {
 	// document
 	NodeType::Document(children) => {
 	    // get to children
 	    for child in children.borrow().iter() {
-		render_node(Rc::clone(&child), max_width, max_height);
+		render_node(child, max_width, max_height);
 	    }
 	},
 	// containers
-	NodeType::Container(tag_name, children, _) => {
-	    // find margins and padding
-	    let mut margin_left = Distance::Absolute(0.);
-	    let mut margin_right = Distance::Absolute(0.);
-	    let mut margin_top = Distance::Absolute(0.);
-	    let mut margin_bottom = Distance::Absolute(0.);
-	    match node.css.borrow().get("margin") {
-		Some(m) => {
-		    let parts: Vec<&str> = m.split(" ").collect();
-		    if parts.len() == 1 {
-			margin_left = Distance::from(parts[0]);
-			margin_right = Distance::from(parts[0]);
-			margin_top = Distance::from(parts[0]);
-			margin_bottom = Distance::from(parts[0]);
-		    } else if parts.len() == 2 {
-			margin_left = Distance::from(parts[1]);
-			margin_right = Distance::from(parts[1]);
-			margin_top = Distance::from(parts[0]);
-			margin_bottom = Distance::from(parts[0]);
-		    } else if parts.len() == 3 {
-			margin_left = Distance::from(parts[1]);
-			margin_right = Distance::from(parts[1]);
-			margin_top = Distance::from(parts[0]);
-			margin_bottom = Distance::from(parts[2]);
-		    } else {
-			margin_left = Distance::from(parts[3]);
-			margin_right = Distance::from(parts[1]);
-			margin_top = Distance::from(parts[0]);
-			margin_bottom = Distance::from(parts[2]);
-		    }
-		},
-		None => {},
-	    };
-	    match node.css.borrow().get("margin-left") {
-		Some(m) => margin_left = Distance::from(m.to_string()),
-		None => {}
-	    };
-	    match node.css.borrow().get("margin-right") {
-		Some(m) => margin_right = Distance::from(m.to_string()),
-		None => {}
-	    };
-	    match node.css.borrow().get("margin-top") {
-		Some(m) => margin_top = Distance::from(m.to_string()),
-		None => {}
-	    };
-	    match node.css.borrow().get("margin-bottom") {
-		Some(m) => margin_bottom = Distance::from(m.to_string()),
-		None => {}
-	    };
-	    let mut padding_left = Distance::Absolute(0.);
-	    let mut padding_right = Distance::Absolute(0.);
-	    let mut padding_top = Distance::Absolute(0.);
-	    let mut padding_bottom = Distance::Absolute(0.);
-	    match node.css.borrow().get("padding") {
-		Some(m) => {
-		    let parts: Vec<&str> = m.split(" ").collect();
-		    if parts.len() == 1 {
-			padding_left = Distance::from(parts[0]);
-			padding_right = Distance::from(parts[0]);
-			padding_top = Distance::from(parts[0]);
-			padding_bottom = Distance::from(parts[0]);
-		    } else if parts.len() == 2 {
-			padding_left = Distance::from(parts[1]);
-			padding_right = Distance::from(parts[1]);
-			padding_top = Distance::from(parts[0]);
-			padding_bottom = Distance::from(parts[0]);
-		    } else if parts.len() == 3 {
-			padding_left = Distance::from(parts[1]);
-			padding_right = Distance::from(parts[1]);
-			padding_top = Distance::from(parts[0]);
-			padding_bottom = Distance::from(parts[2]);
-		    } else {
-			padding_left = Distance::from(parts[3]);
-			padding_right = Distance::from(parts[1]);
-			padding_top = Distance::from(parts[0]);
-			padding_bottom = Distance::from(parts[2]);
-		    }
-		},
-		None => {},
-	    };
-	    match node.css.borrow().get("padding-left") {
-		Some(m) => padding_left = Distance::from(m.to_string()),
-		None => {}
-	    };
-	    match node.css.borrow().get("padding-right") {
-		Some(m) => padding_right = Distance::from(m.to_string()),
-		None => {}
-	    };
-	    match node.css.borrow().get("padding-top") {
-		Some(m) => padding_top = Distance::from(m.to_string()),
-		None => {}
-	    };
-	    match node.css.borrow().get("padding-bottom") {
-		Some(m) => padding_bottom = Distance::from(m.to_string()),
-		None => {}
-	    };
+	NodeType::Container(tag_name, children, _, _) => {
+	    let style = node.style.borrow();
+	    // auto margins are treated as 0 for now
+	    let margin_left = style.margin_left.unwrap_or(Distance::Absolute(0.));
+	    let margin_right = style.margin_right.unwrap_or(Distance::Absolute(0.));
+	    let margin_top = style.margin_top.unwrap_or(Distance::Absolute(0.));
+	    let margin_bottom = style.margin_bottom.unwrap_or(Distance::Absolute(0.));
+	    let padding_left = style.padding_left;
+	    let padding_right = style.padding_right;
+	    let padding_top = style.padding_top;
+	    let padding_bottom = style.padding_bottom;
 	    // get width if specified
-	    let width = match node.css.borrow().get("width") {
-		Some(w) => Distance::from(w.to_string()),
+	    let width = match style.width {
+		Some(w) => w,
 		None => max_width-margin_left-margin_right-padding_left-padding_right,
 	    };
-	    // get height if specified
-	    let height = match node.css.borrow().get("height") {
-		Some(w) => Some(Distance::from(w.to_string())),
-		None => None,
-	    };
-	    // get color if specified or transparent
-	    let color = match node.css.borrow().get("background-color") {
-		Some(c) => get_color(c.to_string()),
-		None => [1.0, 1.0, 1.0, 0.0]
-	    };
-	    // set everything
-	    let layout_box = &mut *node.render.borrow_mut();
-	    let mut content = Block::new();
-	    // weird condition where body's margin is actually padding
-	    if tag_name == "body" {
-		content.padding_left = margin_left;
-		content.padding_right = margin_right;
-		content.padding_top = margin_top;
-		content.padding_bottom = margin_bottom;
-		layout_box.visual_width = width + margin_left + margin_right;
-	    } else {
-		content.margin_left = margin_left;
-		content.margin_right = margin_right;
-		content.margin_top = margin_top;
-		content.margin_bottom = margin_bottom;
-		content.padding_left = padding_left;
-		content.padding_right = padding_right;
-		content.padding_top = padding_top;
-		content.padding_bottom = padding_bottom;
-		layout_box.visual_width = width;
+	    let height = style.height;
+	    let color = style.background_color;
+	    if dirty.layout {
+		// set everything
+		let layout_box = &mut *node.render.borrow_mut();
+		let mut content = Block::new();
+		// weird condition where body's margin is actually padding
+		if tag_name == "body" {
+		    content.padding_left = margin_left;
+		    content.padding_right = margin_right;
+		    content.padding_top = margin_top;
+		    content.padding_bottom = margin_bottom;
+		    layout_box.visual_width = width + margin_left + margin_right;
+		} else {
+		    content.margin_left = margin_left;
+		    content.margin_right = margin_right;
+		    content.margin_top = margin_top;
+		    content.margin_bottom = margin_bottom;
+		    content.padding_left = padding_left;
+		    content.padding_right = padding_right;
+		    content.padding_top = padding_top;
+		    content.padding_bottom = padding_bottom;
+		    layout_box.visual_width = width;
+		}
+		layout_box.visual_height = height;
+		content.color = color;
+		layout_box.content = Content::Solid(Box::new(content));
+		// the space children have might have changed
+		for child in children.borrow().iter() {
+		    let mut child_dirty = child.dirty.get();
+		    child_dirty.layout = true;
+		    child.dirty.set(child_dirty);
+		}
+	    }
+	    // whitespace at the edges of text depends on what's around it, so text is redone when a sibling changes
+	    if children.borrow().iter().any(|child| child.dirty.get().layout) {
+		for child in children.borrow().iter().filter(|child| matches!(child.node_type, NodeType::Text(_))) {
+		    let mut child_dirty = child.dirty.get();
+		    child_dirty.layout = true;
+		    child.dirty.set(child_dirty);
+		}
 	    }
-	    layout_box.visual_height = height;
-	    content.color = color;		
-	    layout_box.content = Content::Solid(content);
 	    let new_max_width = max_width-margin_left-margin_right-padding_left-padding_right;
 	    let new_max_height = max_height-margin_top-margin_bottom-padding_top-padding_bottom;
 	    // get to children
 	    for child in children.borrow().iter() {
-		render_node(Rc::clone(&child), new_max_width, new_max_height);
+		render_node(child, new_max_width, new_max_height);
 	    }
 	},
+	// comments and doctypes aren't shown
+	NodeType::Comment(_) | NodeType::Doctype(_) => {},
+	NodeType::Text(_) if !dirty.layout => {},
 	NodeType::Text(t) => {
 	    // get font/text properties
-	    let color = match node.css.borrow().get("color") {
-		Some(c) => get_color(c.to_string()),
-		None => [0.0, 0.0, 0.0, 1.0]
-	    };
-	    let font_size = match node.css.borrow().get("font-size") {
-		Some(s) => Distance::from(s.to_string()),
-		None => Distance::Absolute(crate::rules::DEFAULT_FONT_SIZE as f64)
-	    };
-	    let weight = match node.css.borrow().get("font-weight") {
-		Some(s) => match s.as_str() {
-		    "bold" => FontWeight::Bold,
-		    _ => FontWeight::Normal,
-		},
-		None => FontWeight::Normal,
-	    };
-	    let slant = match node.css.borrow().get("font-style") {
-		Some(s) => match s.as_str() {
-		    "italic" => FontSlant::Italic,
-		    "oblique" => FontSlant::Oblique,
-		    _ => FontSlant::Normal,
-		},
-		None => FontSlant::Normal,
-	    };
+	    let style = node.style.borrow();
+	    let (strip_start, strip_end) = whitespace_edges(node);
 	    // set label
 	    let layout_box = &mut *node.render.borrow_mut();
 	    layout_box.visual_width = max_width;
-	    layout_box.content = Content::Text(Label{text: t.to_string(),
-						     font_size: font_size,
-						     font_color: color,
-						     weight: weight,
-						     slant: slant});
+	    layout_box.content = Content::Text(Label{text: process_whitespace(&t.borrow(), style.white_space, strip_start, strip_end),
+						     font_size: Distance::Absolute(style.font_size),
+						     font_color: style.color,
+						     font_family: style.font_family.clone(),
+						     weight: style.font_weight,
+						     style: style.font_style,
+						     wrap: style.white_space != WhiteSpace::Pre && style.white_space != WhiteSpace::Nowrap});
 	},
     }
+    dirty.layout = false;
+    dirty.descendant_layout = false;
+    node.dirty.set(dirty);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::web::css::{self, cascade::Origin, media::Device};
+    use crate::renderer::web::html::{parse, apply_css};
+
+    #[test]
+    fn whitespace_collapses_into_single_spaces() {
+	assert_eq!(process_whitespace("  a \t\n b  ", WhiteSpace::Normal, true, true), "a b");
+	assert_eq!(process_whitespace("  a \t\n b  ", WhiteSpace::Normal, false, false), " a b ");
+	assert_eq!(process_whitespace(" \n ", WhiteSpace::Normal, false, false), " ");
+	assert_eq!(process_whitespace("a  b", WhiteSpace::Nowrap, true, true), "a b");
+    }
+
+    #[test]
+    fn pre_keeps_whitespace() {
+	assert_eq!(process_whitespace(" a\t b\n", WhiteSpace::Pre, true, true), " a\t b\n");
+	assert_eq!(process_whitespace(" a\t b\n", WhiteSpace::PreWrap, true, true), " a\t b\n");
+    }
+
+    #[test]
+    fn pre_line_keeps_newlines() {
+	assert_eq!(process_whitespace(" a  \n  b \n", WhiteSpace::PreLine, true, true), "a\nb\n");
+    }
+
+    #[test]
+    fn carriage_returns_become_newlines() {
+	assert_eq!(process_whitespace("a\r\nb\rc", WhiteSpace::Pre, true, true), "a\nb\nc");
+	assert_eq!(process_whitespace("a\r\nb", WhiteSpace::PreLine, true, true), "a\nb");
+	assert_eq!(process_whitespace("a\r\n\tb", WhiteSpace::Normal, true, true), "a b");
+    }
+
+    #[test]
+    fn boxes_get_their_values_from_the_style() {
+	let document = parse("<div style='margin: 5px auto 1em; padding: 2px 3px; background-color: red; width: 50px; color: blue'>x</div>".to_string());
+	let stylesheet = css::parse("div { display: block; font-size: 20px }".to_string(), Origin::UserAgent);
+	apply_css(&stylesheet.rules.iter().collect::<Vec<_>>(), &document, &Device::default());
+	render_node(&document, Distance::Relative(1.), Distance::Relative(1.));
+	let div = document.query_selector("div").unwrap();
+	let render = div.render.borrow();
+	let Content::Solid(block) = &render.content else {
+	    panic!("div wasn't rendered as a block");
+	};
+	// auto margins are 0 for now
+	assert_eq!((block.margin_top, block.margin_right, block.margin_bottom, block.margin_left),
+		   (Distance::Absolute(5.), Distance::Absolute(0.), Distance::Absolute(20.), Distance::Absolute(0.)));
+	assert_eq!((block.padding_top, block.padding_right, block.padding_bottom, block.padding_left),
+		   (Distance::Absolute(2.), Distance::Absolute(3.), Distance::Absolute(2.), Distance::Absolute(3.)));
+	assert_eq!(block.color, [1., 0., 0., 1.]);
+	assert_eq!(render.visual_width, Distance::Absolute(50.));
+	assert_eq!(render.visual_height, None);
+	let text = Rc::clone(&div.children().borrow()[0]);
+	let text = text.render.borrow();
+	let Content::Text(label) = &text.content else {
+	    panic!("text wasn't rendered as a label");
+	};
+	assert_eq!(label.text, "x");
+	assert_eq!(label.font_size, Distance::Absolute(20.));
+	assert_eq!(label.font_color, [0., 0., 1., 1.]);
+	assert!(label.wrap);
+    }
+
+    // the text each text node in a document is rendered with
+    fn rendered_text(html: &str) -> Vec<String> {
+	let document = parse(html.to_string());
+	let stylesheet = css::parse("p, div { display: block }".to_string(), Origin::UserAgent);
+	apply_css(&stylesheet.rules.iter().collect::<Vec<_>>(), &document, &Device::default());
+	render_node(&document, Distance::Relative(1.), Distance::Relative(1.));
+	let mut texts = Vec::new();
+	let mut nodes = vec![document];
+	while let Some(node) = nodes.pop() {
+	    if let Content::Text(label) = &node.render.borrow().content {
+		texts.push(label.text.clone());
+	    }
+	    if node.is_element() || matches!(node.node_type, NodeType::Document(_)) {
+		nodes.extend(node.children().borrow().iter().rev().map(Rc::clone));
+	    }
+	}
+	texts
+    }
+
+    #[test]
+    fn whitespace_is_only_stripped_at_block_edges() {
+	assert_eq!(rendered_text("<p> a <b>b</b> c </p>"), ["a ", "b", " c"]);
+	assert_eq!(rendered_text("<p>a <b> b</b></p>"), ["a ", "b"]);
+	assert_eq!(rendered_text("<div>a <p>b</p> c</div>"), ["a", "b", "c"]);
+    }
 }
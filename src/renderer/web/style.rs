@@ -0,0 +1,1068 @@
+use crate::rules;
+use crate::renderer::layout::Distance;
+use crate::renderer::web::css::{self, shorthand, tokenizer::{self, Token}, ComponentValue, Declaration};
+use crate::renderer::web::font;
+use crate::renderer::web::css::media::Device;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// how an element is laid out
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Display {
+    Inline,
+    Block,
+    InlineBlock,
+    ListItem,
+    Flex,
+    InlineFlex,
+    Grid,
+    InlineGrid,
+    Table,
+    InlineTable,
+    TableRowGroup,
+    TableHeaderGroup,
+    TableFooterGroup,
+    TableRow,
+    TableCell,
+    TableColumnGroup,
+    TableColumn,
+    TableCaption,
+    // the element's children are laid out as if they were in its place
+    Contents,
+    None,
+}
+
+// how an element is positioned
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Position {
+    Static,
+    Relative,
+    Absolute,
+    Fixed,
+    Sticky,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+// how whitespace in text is handled
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WhiteSpace {
+    Normal,
+    Pre,
+    Nowrap,
+    PreWrap,
+    PreLine,
+}
+
+// the css properties of a node, worked out into values that layout can use
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedStyle {
+    pub display: Display,
+    pub position: Position,
+    // margins that are None are auto
+    pub margin_top: Option<Distance>,
+    pub margin_right: Option<Distance>,
+    pub margin_bottom: Option<Distance>,
+    pub margin_left: Option<Distance>,
+    pub padding_top: Distance,
+    pub padding_right: Distance,
+    pub padding_bottom: Distance,
+    pub padding_left: Distance,
+    // None is auto
+    pub width: Option<Distance>,
+    pub height: Option<Distance>,
+    // rgba
+    pub color: [f64;4],
+    pub background_color: [f64;4],
+    // in pixels
+    pub font_size: f64,
+    // font size of the root element, for rem
+    pub root_font_size: f64,
+    // families to look for fonts in, in order (the default font is used if none of them have any)
+    pub font_family: Vec<String>,
+    // from 1 to 1000, where normal is 400 and bold is 700
+    pub font_weight: u16,
+    pub font_style: FontStyle,
+    pub white_space: WhiteSpace,
+    // custom properties (like --brand-color), with their var()s already substituted
+    pub custom_properties: Rc<HashMap<String, String>>,
+}
+// initial values of every property
+impl Default for ComputedStyle {
+    fn default() -> ComputedStyle {
+	ComputedStyle{display: Display::Inline,
+		      position: Position::Static,
+		      margin_top: Some(Distance::Absolute(0.)),
+		      margin_right: Some(Distance::Absolute(0.)),
+		      margin_bottom: Some(Distance::Absolute(0.)),
+		      margin_left: Some(Distance::Absolute(0.)),
+		      padding_top: Distance::Absolute(0.),
+		      padding_right: Distance::Absolute(0.),
+		      padding_bottom: Distance::Absolute(0.),
+		      padding_left: Distance::Absolute(0.),
+		      width: None,
+		      height: None,
+		      color: [0.0, 0.0, 0.0, 1.0],
+		      background_color: [1.0, 1.0, 1.0, 0.0],
+		      font_size: rules::DEFAULT_FONT_SIZE as f64,
+		      root_font_size: rules::DEFAULT_FONT_SIZE as f64,
+		      font_family: Vec::new(),
+		      font_weight: 400,
+		      font_style: FontStyle::Normal,
+		      white_space: WhiteSpace::Normal,
+		      custom_properties: Rc::default()}
+    }
+}
+
+impl ComputedStyle {
+    // works out the style of a node from its css properties, the style of its parent (None for the root), and the screen it's on
+    // (values that are invalid are inherited for inherited properties, and initial for the rest)
+    pub fn compute(declarations: &HashMap<String, Declaration>, parent: Option<&ComputedStyle>, device: &Device) -> ComputedStyle {
+	let initial = ComputedStyle::default();
+	let root = parent.is_none();
+	let parent = parent.unwrap_or(&initial);
+	let properties = &declarations.iter()
+	    .map(|(name, declaration)| (name.clone(), css::serialize(&declaration.value)))
+	    .collect::<HashMap<String, String>>();
+	// var()s are substituted before anything else, and properties with var()s that don't work are unset
+	// (longhands of shorthands with var()s get their part of the shorthand once it's substituted)
+	let custom_properties = compute_custom_properties(properties, &parent.custom_properties);
+	let properties = &properties.iter()
+	    .filter(|(name, _)| !name.starts_with("--"))
+	    .map(|(name, value)| {
+		let value = substitute_vars(value, &mut |name| custom_properties.get(name).cloned());
+		let value = match &declarations[name].shorthand {
+		    Some(shorthand) => value.and_then(|value| shorthand::longhand(shorthand, &value, name)),
+		    None => value,
+		};
+		(name.clone(), value.unwrap_or_else(|| String::from("unset")))
+	    })
+	    .collect::<HashMap<String, String>>();
+	let values = |name: &str| properties.get(name).map(|value| parse_values(value)).unwrap_or_default();
+	let mut style = initial.clone();
+	// every property starts out inherited or initial, depending on its keyword or whether it's an inherited property
+	// (other values are worked out below, and act like unset if they turn out not to work once their var()s are substituted)
+	for name in PROPERTIES.iter() {
+	    let inherited = rules::INHERITED_PROPERTIES.contains(name);
+	    let inherit = match properties.get(*name).map(|value| value.trim().to_ascii_lowercase()).as_deref() {
+		Some("inherit") => true,
+		Some("initial") => false,
+		_ => inherited,
+	    };
+	    copy_property(&mut style, if inherit { parent } else { &initial }, name);
+	}
+	// font size goes first, since em lengths depend on it (and for font-size itself, em is the parent's font size)
+	let mut units = Units{font_size: parent.font_size,
+			      root_font_size: parent.root_font_size,
+			      viewport_width: device.width,
+			      viewport_height: device.height};
+	if let [value] = values("font-size").as_slice() {
+	    style.font_size = parse_font_size(value, units).unwrap_or(style.font_size);
+	}
+	units.font_size = style.font_size;
+	// rem on the root element is the initial font size, and for everything else it's the root's font size
+	style.root_font_size = if root { style.font_size } else { parent.root_font_size };
+	if let [value] = values("color").as_slice() {
+	    style.color = parse_color(value, parent.color).unwrap_or(style.color);
+	}
+	if let [value] = values("background-color").as_slice() {
+	    style.background_color = parse_color(value, style.color).unwrap_or(style.background_color);
+	}
+	if let [ComponentValue::Token(Token::Ident(display))] = values("display").as_slice() {
+	    style.display = parse_display(display).unwrap_or(style.display);
+	}
+	if let [ComponentValue::Token(Token::Ident(position))] = values("position").as_slice() {
+	    style.position = parse_position(position).unwrap_or(style.position);
+	}
+	if let [value] = values("font-weight").as_slice() {
+	    style.font_weight = parse_font_weight(value, parent.font_weight).unwrap_or(style.font_weight);
+	}
+	if let [ComponentValue::Token(Token::Ident(font_style))] = values("font-style").as_slice() {
+	    style.font_style = parse_font_style(font_style).unwrap_or(style.font_style);
+	}
+	if let Some(font_family) = font::parse_family_names(&values("font-family")) {
+	    style.font_family = font_family;
+	}
+	if let [ComponentValue::Token(Token::Ident(white_space))] = values("white-space").as_slice() {
+	    style.white_space = match white_space.to_ascii_lowercase().as_str() {
+		"normal" => WhiteSpace::Normal,
+		"pre" => WhiteSpace::Pre,
+		"nowrap" => WhiteSpace::Nowrap,
+		"pre-wrap" => WhiteSpace::PreWrap,
+		"pre-line" => WhiteSpace::PreLine,
+		_ => style.white_space,
+	    };
+	}
+	for (name, margin) in [("margin-top", &mut style.margin_top), ("margin-right", &mut style.margin_right), ("margin-bottom", &mut style.margin_bottom), ("margin-left", &mut style.margin_left)] {
+	    if let [value] = values(name).as_slice() {
+		*margin = parse_length_or_auto(value, units).unwrap_or(*margin);
+	    }
+	}
+	for (name, padding) in [("padding-top", &mut style.padding_top), ("padding-right", &mut style.padding_right), ("padding-bottom", &mut style.padding_bottom), ("padding-left", &mut style.padding_left)] {
+	    if let [value] = values(name).as_slice() {
+		*padding = parse_non_negative_length(value, units).unwrap_or(*padding);
+	    }
+	}
+	for (name, size) in [("width", &mut style.width), ("height", &mut style.height)] {
+	    if let [value] = values(name).as_slice() {
+		*size = parse_size(value, units).unwrap_or(*size);
+	    }
+	}
+	style.custom_properties = custom_properties;
+	style
+    }
+}
+
+// works out the custom properties of an element, which are inherited unless the element sets them
+fn compute_custom_properties(properties: &HashMap<String, String>, parent: &Rc<HashMap<String, String>>) -> Rc<HashMap<String, String>> {
+    let names = properties.keys().filter(|name| name.starts_with("--")).collect::<Vec<&String>>();
+    if names.is_empty() {
+	return Rc::clone(parent);
+    }
+    let mut resolved = HashMap::new();
+    for name in names {
+	resolve_custom_property(name, properties, parent, &mut resolved, &mut Vec::new());
+    }
+    let mut custom_properties = (**parent).clone();
+    for (name, value) in resolved {
+	match value {
+	    Some(value) => custom_properties.insert(name.to_string(), value),
+	    None => custom_properties.remove(name),
+	};
+    }
+    Rc::new(custom_properties)
+}
+// works out the value of a custom property, which is None if it's invalid
+// (resolving is the custom properties being worked out that led to this one, so that cycles can be found)
+fn resolve_custom_property<'a>(name: &str, properties: &'a HashMap<String, String>, parent: &HashMap<String, String>,
+			       resolved: &mut HashMap<&'a str, Option<String>>, resolving: &mut Vec<&'a str>) -> Option<String> {
+    let (name, value) = match properties.get_key_value(name) {
+	Some((name, value)) => (name.as_str(), value),
+	None => return parent.get(name).cloned(),
+    };
+    if let Some(value) = resolved.get(name) {
+	return value.clone();
+    }
+    // custom properties that depend on each other in a circle are all invalid
+    if let Some(start) = resolving.iter().position(|n| *n == name) {
+	for n in resolving[start..].iter() {
+	    resolved.insert(n, None);
+	}
+	return None;
+    }
+    let value = match value.trim().to_ascii_lowercase().as_str() {
+	"initial" => None,
+	"inherit" | "unset" => parent.get(name).cloned(),
+	_ => {
+	    resolving.push(name);
+	    let value = substitute_vars(value, &mut |reference| resolve_custom_property(reference, properties, parent, resolved, resolving));
+	    resolving.pop();
+	    value
+	},
+    };
+    // it might have turned out to be part of a cycle while its var()s were substituted
+    let value = match resolved.get(name) {
+	Some(None) => None,
+	_ => value,
+    };
+    resolved.insert(name, value.clone());
+    value
+}
+
+// replaces var()s in a value with the custom properties they refer to (or their fallbacks),
+// giving None if one of them refers to a custom property that isn't set and doesn't have a fallback
+fn substitute_vars(value: &str, lookup: &mut dyn FnMut(&str) -> Option<String>) -> Option<String> {
+    if !value.to_ascii_lowercase().contains("var(") {
+	return Some(value.to_string());
+    }
+    substitute(&css::component_values(&tokenizer::tokenize(value)), lookup).map(|values| css::serialize(&values))
+}
+fn substitute(values: &[ComponentValue], lookup: &mut dyn FnMut(&str) -> Option<String>) -> Option<Vec<ComponentValue>> {
+    let mut substituted = Vec::new();
+    for value in values {
+	match value {
+	    // var(--name) or var(--name, fallback)
+	    ComponentValue::Function(function, arguments) if function.eq_ignore_ascii_case("var") => {
+		let (reference, fallback) = match arguments.iter().position(|v| v == &ComponentValue::Token(Token::Comma)) {
+		    Some(comma) => (&arguments[..comma], Some(&arguments[comma+1..])),
+		    None => (&arguments[..], None),
+		};
+		let name = match reference.iter().filter(|v| v != &&ComponentValue::Token(Token::Whitespace)).collect::<Vec<_>>().as_slice() {
+		    [ComponentValue::Token(Token::Ident(name))] if name.starts_with("--") => name.to_string(),
+		    _ => return None,
+		};
+		match lookup(&name) {
+		    Some(value) => substituted.append(&mut css::component_values(&tokenizer::tokenize(&value))),
+		    None => substituted.append(&mut substitute(fallback?, lookup)?),
+		}
+	    },
+	    ComponentValue::Function(function, arguments) => substituted.push(ComponentValue::Function(function.to_string(), substitute(arguments, lookup)?)),
+	    ComponentValue::Block(open, contents) => substituted.push(ComponentValue::Block(*open, substitute(contents, lookup)?)),
+	    _ => substituted.push(value.clone()),
+	}
+    }
+    Some(substituted)
+}
+
+// properties that the computed style has
+const PROPERTIES: [&str;19] = ["display", "position", "margin-top", "margin-right", "margin-bottom", "margin-left",
+			       "padding-top", "padding-right", "padding-bottom", "padding-left", "width", "height",
+			       "color", "background-color", "font-size", "font-family", "font-weight", "font-style", "white-space"];
+
+// copies a property from one style to another, for inheritance and initial values
+fn copy_property(to: &mut ComputedStyle, from: &ComputedStyle, name: &str) {
+    match name {
+	"display" => to.display = from.display,
+	"position" => to.position = from.position,
+	"margin-top" => to.margin_top = from.margin_top,
+	"margin-right" => to.margin_right = from.margin_right,
+	"margin-bottom" => to.margin_bottom = from.margin_bottom,
+	"margin-left" => to.margin_left = from.margin_left,
+	"padding-top" => to.padding_top = from.padding_top,
+	"padding-right" => to.padding_right = from.padding_right,
+	"padding-bottom" => to.padding_bottom = from.padding_bottom,
+	"padding-left" => to.padding_left = from.padding_left,
+	"width" => to.width = from.width,
+	"height" => to.height = from.height,
+	"color" => to.color = from.color,
+	"background-color" => to.background_color = from.background_color,
+	"font-size" => to.font_size = from.font_size,
+	"font-family" => to.font_family = from.font_family.clone(),
+	"font-weight" => to.font_weight = from.font_weight,
+	"font-style" => to.font_style = from.font_style,
+	"white-space" => to.white_space = from.white_space,
+	_ => {},
+    }
+}
+
+// whether a value can be used for a property, so that declarations with values that can't be are dropped
+// (values with var() can only be checked once it's substituted, so they're kept)
+pub fn is_valid(name: &str, value: &[ComponentValue]) -> bool {
+    let values = value.iter().filter(|v| v != &&ComponentValue::Token(Token::Whitespace)).collect::<Vec<&ComponentValue>>();
+    if has_var(value) {
+	return true;
+    }
+    if let [ComponentValue::Token(Token::Ident(keyword))] = values.as_slice() {
+	if ["inherit", "initial", "unset", "revert"].contains(&keyword.to_ascii_lowercase().as_str()) {
+	    return true;
+	}
+    }
+    // lengths only have to parse here, so what they're relative to doesn't matter
+    let units = Units{font_size: rules::DEFAULT_FONT_SIZE as f64,
+		      root_font_size: rules::DEFAULT_FONT_SIZE as f64,
+		      viewport_width: 0.,
+		      viewport_height: 0.};
+    let single = |check: &dyn Fn(&ComponentValue) -> bool| matches!(values.as_slice(), [value] if check(value));
+    match name {
+	"color" | "background-color" | "border-top-color" | "border-right-color" | "border-bottom-color" | "border-left-color" => single(&|v| parse_color(v, [0.0;4]).is_some()),
+	"margin-top" | "margin-right" | "margin-bottom" | "margin-left" => single(&|v| parse_length_or_auto(v, units).is_some()),
+	"width" | "height" => single(&|v| parse_size(v, units).is_some()),
+	"padding-top" | "padding-right" | "padding-bottom" | "padding-left" => single(&|v| parse_non_negative_length(v, units).is_some()),
+	"border-top-width" | "border-right-width" | "border-bottom-width" | "border-left-width" => single(&|v| {
+	    matches!(v, ComponentValue::Token(Token::Ident(width)) if ["thin", "medium", "thick"].contains(&width.to_ascii_lowercase().as_str()))
+		|| parse_non_negative_length(v, units).is_some()
+	}),
+	"border-top-style" | "border-right-style" | "border-bottom-style" | "border-left-style" => single(&|v| {
+	    matches!(v, ComponentValue::Token(Token::Ident(border_style)) if BORDER_STYLES.contains(&border_style.to_ascii_lowercase().as_str()))
+	}),
+	"font-size" => single(&|v| parse_font_size(v, units).is_some()),
+	// normal, or a number (of the font size) or length that isn't negative
+	"line-height" => single(&|v| match v {
+	    ComponentValue::Token(Token::Ident(normal)) => normal.eq_ignore_ascii_case("normal"),
+	    ComponentValue::Token(Token::Number(n, _)) => *n >= 0.,
+	    ComponentValue::Function(name, arguments) if matches!(parse_math_function(name, arguments, units), Some(CalcValue::Number(_))) => true,
+	    _ => parse_non_negative_length(v, units).is_some(),
+	}),
+	"font-weight" => single(&|v| parse_font_weight(v, 400).is_some()),
+	"font-style" => single(&|v| matches!(v, ComponentValue::Token(Token::Ident(font_style)) if parse_font_style(font_style).is_some())),
+	"font-family" => font::parse_family_names(&values.into_iter().cloned().collect::<Vec<ComponentValue>>()).is_some(),
+	_ => true,
+    }
+}
+const BORDER_STYLES: [&str;10] = ["none", "hidden", "dotted", "dashed", "solid", "double", "groove", "ridge", "inset", "outset"];
+pub fn has_var(values: &[ComponentValue]) -> bool {
+    values.iter().any(|value| match value {
+	ComponentValue::Function(name, _) if name.eq_ignore_ascii_case("var") => true,
+	ComponentValue::Function(_, values) | ComponentValue::Block(_, values) => has_var(values),
+	_ => false,
+    })
+}
+
+// splits a value into its parts, like "1em auto" into 1em and auto
+fn parse_values(value: &str) -> Vec<ComponentValue> {
+    css::component_values(&tokenizer::tokenize(value)).into_iter()
+	.filter(|v| v != &ComponentValue::Token(Token::Whitespace))
+	.collect()
+}
+
+// what relative lengths are relative to
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Units {
+    // for em, ex and ch
+    font_size: f64,
+    // for rem
+    root_font_size: f64,
+    // for vw, vh, vmin and vmax
+    viewport_width: f64,
+    viewport_height: f64,
+}
+
+// parses a length like 10px or 50%
+fn parse_length(value: &ComponentValue, units: Units) -> Option<Distance> {
+    match value {
+	ComponentValue::Token(Token::Dimension(n, _, unit)) => Some(Distance::Absolute(n * match unit.to_ascii_lowercase().as_str() {
+	    "px" => 1.,
+	    // absolute units, where an inch is 96 pixels
+	    "in" => 96.,
+	    "cm" => 96./2.54,
+	    "mm" => 96./25.4,
+	    "q" => 96./101.6,
+	    "pt" => 96./72.,
+	    "pc" => 16.,
+	    "em" => units.font_size,
+	    "rem" => units.root_font_size,
+	    // the height of an x and the width of a 0 depend on the font, so they are guessed as half an em
+	    "ex" | "ch" => units.font_size/2.,
+	    "vw" => units.viewport_width/100.,
+	    "vh" => units.viewport_height/100.,
+	    "vmin" => units.viewport_width.min(units.viewport_height)/100.,
+	    "vmax" => units.viewport_width.max(units.viewport_height)/100.,
+	    _ => return None,
+	})),
+	ComponentValue::Token(Token::Percentage(n)) => Some(Distance::Relative(n/100.)),
+	// lengths of 0 don't need a unit
+	ComponentValue::Token(Token::Number(n, _)) if *n == 0. => Some(Distance::Absolute(0.)),
+	ComponentValue::Function(name, arguments) => match parse_math_function(name, arguments, units)? {
+	    CalcValue::Length(distance) => Some(distance),
+	    CalcValue::Number(_) => None,
+	},
+	_ => None,
+    }
+}
+
+// what calc() and the other math functions work with
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum CalcValue {
+    Number(f64),
+    Length(Distance),
+}
+
+// parses calc(), min(), max() and clamp()
+// (math that can't be kept as a distance, which is pixels plus a percent or a clamp of those, is invalid instead of being
+// approximated, like min() of three lengths that can't be compared, a clamp() with a min() in it, or two min()s added)
+fn parse_math_function(name: &str, arguments: &[ComponentValue], units: Units) -> Option<CalcValue> {
+    let arguments = arguments.split(|v| v == &ComponentValue::Token(Token::Comma))
+	.map(|argument| parse_calc_sum(argument, units))
+	.collect::<Option<Vec<CalcValue>>>()?;
+    // the arguments all have to be numbers, or all be lengths
+    let numbers = arguments.iter().map(|argument| match argument {
+	CalcValue::Number(n) => Some(*n),
+	CalcValue::Length(_) => None,
+    }).collect::<Option<Vec<f64>>>();
+    let lengths = arguments.iter().map(|argument| match argument {
+	CalcValue::Length(distance) => Some(*distance),
+	CalcValue::Number(_) => None,
+    }).collect::<Option<Vec<Distance>>>();
+    match (name.to_ascii_lowercase().as_str(), numbers, lengths) {
+	("calc", _, _) if arguments.len() == 1 => Some(arguments[0]),
+	("min", Some(numbers), _) => numbers.into_iter().reduce(f64::min).map(CalcValue::Number),
+	("min", _, Some(lengths)) => Distance::min(&lengths).map(CalcValue::Length),
+	("max", Some(numbers), _) => numbers.into_iter().reduce(f64::max).map(CalcValue::Number),
+	("max", _, Some(lengths)) => Distance::max(&lengths).map(CalcValue::Length),
+	// if the min is bigger than the max, the min wins
+	("clamp", Some(numbers), _) if numbers.len() == 3 => Some(CalcValue::Number(numbers[1].min(numbers[2]).max(numbers[0]))),
+	("clamp", _, Some(lengths)) if lengths.len() == 3 => Distance::clamp(lengths[0], lengths[1], lengths[2]).map(CalcValue::Length),
+	_ => None,
+    }
+}
+// parses values that are added and subtracted, like 100% - 2em (where + and - need whitespace around them)
+fn parse_calc_sum(values: &[ComponentValue], units: Units) -> Option<CalcValue> {
+    let mut sum = None;
+    let mut sign = 1.;
+    let mut start = 0;
+    for (index, value) in values.iter().enumerate().chain([(values.len(), &ComponentValue::Token(Token::Delim('+')))]) {
+	if let ComponentValue::Token(Token::Delim(operator @ ('+' | '-'))) = value {
+	    let whitespace = |index: Option<usize>| index.and_then(|index| values.get(index)) == Some(&ComponentValue::Token(Token::Whitespace));
+	    if index < values.len() && !(whitespace(index.checked_sub(1)) && whitespace(Some(index+1))) {
+		return None;
+	    }
+	    // everything between a + or - and the next one is multiplied and divided first
+	    let product = match parse_calc_product(&values[start..index], units)? {
+		CalcValue::Number(n) => CalcValue::Number(n*sign),
+		CalcValue::Length(distance) => CalcValue::Length(distance.checked_scale(sign)?),
+	    };
+	    sum = Some(match (sum, product) {
+		(None, product) => product,
+		(Some(CalcValue::Number(a)), CalcValue::Number(b)) => CalcValue::Number(a+b),
+		(Some(CalcValue::Length(a)), CalcValue::Length(b)) => CalcValue::Length(a.checked_add(b)?),
+		// numbers and lengths can't be added together
+		_ => return None,
+	    });
+	    sign = if *operator == '-' { -1. } else { 1. };
+	    start = index+1;
+	}
+    }
+    sum
+}
+// parses values that are multiplied and divided, like 2 * 1em (where one side of * and the right side of / have to be numbers)
+fn parse_calc_product(values: &[ComponentValue], units: Units) -> Option<CalcValue> {
+    let values = values.iter().filter(|v| v != &&ComponentValue::Token(Token::Whitespace)).collect::<Vec<&ComponentValue>>();
+    let mut product = parse_calc_value(values.first()?, units)?;
+    for pair in values[1..].chunks(2) {
+	let [operator, value] = pair else {
+	    return None;
+	};
+	product = match (operator, product, parse_calc_value(value, units)?) {
+	    (ComponentValue::Token(Token::Delim('*')), CalcValue::Number(a), CalcValue::Number(b)) => CalcValue::Number(a*b),
+	    (ComponentValue::Token(Token::Delim('*')), CalcValue::Number(n), CalcValue::Length(distance)) |
+	    (ComponentValue::Token(Token::Delim('*')), CalcValue::Length(distance), CalcValue::Number(n)) => CalcValue::Length(distance.checked_scale(n)?),
+	    (ComponentValue::Token(Token::Delim('/')), CalcValue::Number(a), CalcValue::Number(b)) if b != 0. => CalcValue::Number(a/b),
+	    (ComponentValue::Token(Token::Delim('/')), CalcValue::Length(distance), CalcValue::Number(n)) if n != 0. => CalcValue::Length(distance.checked_scale(1./n)?),
+	    _ => return None,
+	};
+    }
+    Some(product)
+}
+// parses a number, length, (...) or math function in a math function
+fn parse_calc_value(value: &ComponentValue, units: Units) -> Option<CalcValue> {
+    match value {
+	ComponentValue::Token(Token::Number(n, _)) => Some(CalcValue::Number(*n)),
+	ComponentValue::Block('(', contents) => parse_calc_sum(contents, units),
+	ComponentValue::Function(name, arguments) => parse_math_function(name, arguments, units),
+	_ => parse_length(value, units).map(CalcValue::Length),
+    }
+}
+// parses a length, or auto (which is None)
+fn parse_length_or_auto(value: &ComponentValue, units: Units) -> Option<Option<Distance>> {
+    match value {
+	ComponentValue::Token(Token::Ident(ident)) if ident.eq_ignore_ascii_case("auto") => Some(None),
+	_ => parse_length(value, units).map(Some),
+    }
+}
+// parses a length that can't be negative, like a padding
+// (math functions still can be, since whether they are often depends on what their percents are of)
+fn parse_non_negative_length(value: &ComponentValue, units: Units) -> Option<Distance> {
+    match value {
+	ComponentValue::Token(Token::Dimension(n, _, _) | Token::Percentage(n)) if *n < 0. => None,
+	_ => parse_length(value, units),
+    }
+}
+// parses a width or height, which is a length that can't be negative, or auto (which is None)
+fn parse_size(value: &ComponentValue, units: Units) -> Option<Option<Distance>> {
+    match value {
+	ComponentValue::Token(Token::Ident(ident)) if ident.eq_ignore_ascii_case("auto") => Some(None),
+	_ => parse_non_negative_length(value, units).map(Some),
+    }
+}
+
+// parses a font size in pixels, which can be relative to the parent's font size (which units has as its font size)
+fn parse_font_size(value: &ComponentValue, units: Units) -> Option<f64> {
+    let parent_font_size = units.font_size;
+    let medium = rules::DEFAULT_FONT_SIZE as f64;
+    match value {
+	ComponentValue::Token(Token::Ident(keyword)) => match keyword.to_ascii_lowercase().as_str() {
+	    "xx-small" => Some(medium*3./5.),
+	    "x-small" => Some(medium*3./4.),
+	    "small" => Some(medium*8./9.),
+	    "medium" => Some(medium),
+	    "large" => Some(medium*6./5.),
+	    "x-large" => Some(medium*3./2.),
+	    "xx-large" => Some(medium*2.),
+	    "xxx-large" => Some(medium*3.),
+	    "smaller" => Some(parent_font_size/1.2),
+	    "larger" => Some(parent_font_size*1.2),
+	    _ => None,
+	},
+	// em and percentages are relative to the parent's font size, and sizes can't be negative
+	_ => {
+	    let size = parse_length(value, units)?.to_pixels(parent_font_size);
+	    match value {
+		// math functions are kept in range instead of being invalid
+		ComponentValue::Function(..) => Some(size.max(0.)),
+		_ if size >= 0. => Some(size),
+		_ => None,
+	    }
+	},
+    }
+}
+
+// parses a font weight, where bolder and lighter are relative to the parent's
+fn parse_font_weight(value: &ComponentValue, parent_font_weight: u16) -> Option<u16> {
+    match value {
+	ComponentValue::Token(Token::Number(n, _)) if (1.0..=1000.0).contains(n) => Some(*n as u16),
+	ComponentValue::Token(Token::Ident(keyword)) => match keyword.to_ascii_lowercase().as_str() {
+	    "normal" => Some(400),
+	    "bold" => Some(700),
+	    "bolder" => Some(match parent_font_weight {
+		0..=349 => 400,
+		350..=549 => 700,
+		_ => 900,
+	    }),
+	    "lighter" => Some(match parent_font_weight {
+		0..=549 => 100,
+		550..=749 => 400,
+		_ => 700,
+	    }),
+	    _ => None,
+	},
+	_ => None,
+    }
+}
+
+fn parse_display(display: &str) -> Option<Display> {
+    Some(match display.to_ascii_lowercase().as_str() {
+	"inline" => Display::Inline,
+	"block" => Display::Block,
+	"inline-block" => Display::InlineBlock,
+	"list-item" => Display::ListItem,
+	"flex" => Display::Flex,
+	"inline-flex" => Display::InlineFlex,
+	"grid" => Display::Grid,
+	"inline-grid" => Display::InlineGrid,
+	"table" => Display::Table,
+	"inline-table" => Display::InlineTable,
+	"table-row-group" => Display::TableRowGroup,
+	"table-header-group" => Display::TableHeaderGroup,
+	"table-footer-group" => Display::TableFooterGroup,
+	"table-row" => Display::TableRow,
+	"table-cell" => Display::TableCell,
+	"table-column-group" => Display::TableColumnGroup,
+	"table-column" => Display::TableColumn,
+	"table-caption" => Display::TableCaption,
+	"contents" => Display::Contents,
+	"none" => Display::None,
+	_ => return None,
+    })
+}
+
+pub fn parse_font_style(font_style: &str) -> Option<FontStyle> {
+    Some(match font_style.to_ascii_lowercase().as_str() {
+	"normal" => FontStyle::Normal,
+	"italic" => FontStyle::Italic,
+	"oblique" => FontStyle::Oblique,
+	_ => return None,
+    })
+}
+
+fn parse_position(position: &str) -> Option<Position> {
+    Some(match position.to_ascii_lowercase().as_str() {
+	"static" => Position::Static,
+	"relative" => Position::Relative,
+	"absolute" => Position::Absolute,
+	"fixed" => Position::Fixed,
+	"sticky" => Position::Sticky,
+	_ => return None,
+    })
+}
+
+// parses a color into rgba (current color is what currentcolor is), following https://www.w3.org/TR/css-color-4/
+pub fn parse_color(value: &ComponentValue, current_color: [f64;4]) -> Option<[f64;4]> {
+    match value {
+	ComponentValue::Token(Token::Ident(name)) => {
+	    let name = name.to_ascii_lowercase();
+	    match name.as_str() {
+		"transparent" => Some([0.0, 0.0, 0.0, 0.0]),
+		"currentcolor" => Some(current_color),
+		_ => {
+		    let (_, hex) = rules::DEFAULT_COLORS.iter().chain(rules::SYSTEM_COLORS.iter()).find(|(key, _)| *key == name)?;
+		    parse_hex_color(hex.trim_start_matches('#'))
+		},
+	    }
+	},
+	ComponentValue::Token(Token::Hash(hex, _)) => parse_hex_color(hex),
+	ComponentValue::Function(name, arguments) => {
+	    let (channels, alpha, legacy) = color_arguments(arguments)?;
+	    let alpha = match alpha {
+		Some(alpha) => color_channel(alpha, 1., legacy)?.clamp(0., 1.),
+		None => 1.,
+	    };
+	    let [r, g, b] = match name.to_ascii_lowercase().as_str() {
+		"rgb" | "rgba" => {
+		    // the old syntax with commas can't mix numbers and percentages
+		    if legacy && !(channels.iter().all(|c| matches!(c, ComponentValue::Token(Token::Number(..))))
+				   || channels.iter().all(|c| matches!(c, ComponentValue::Token(Token::Percentage(_))))) {
+			return None;
+		    }
+		    let channel = |value| color_channel(value, 255., legacy).map(|n| n.clamp(0., 255.)/255.);
+		    [channel(channels[0])?, channel(channels[1])?, channel(channels[2])?]
+		},
+		"hsl" | "hsla" => {
+		    // saturation and lightness have to be percentages in the old syntax
+		    if legacy && !channels[1..].iter().all(|c| matches!(c, ComponentValue::Token(Token::Percentage(_)))) {
+			return None;
+		    }
+		    let percent = |value| color_channel(value, 100., legacy).map(|n| n.clamp(0., 100.)/100.);
+		    hsl_to_rgb(hue(channels[0], legacy)?, percent(channels[1])?, percent(channels[2])?)
+		},
+		// hwb only has the new syntax
+		"hwb" if !legacy => {
+		    let percent = |value| color_channel(value, 100., legacy).map(|n| n.clamp(0., 100.)/100.);
+		    hwb_to_rgb(hue(channels[0], legacy)?, percent(channels[1])?, percent(channels[2])?)
+		},
+		_ => return None,
+	    };
+	    Some([r, g, b, alpha])
+	},
+	_ => None,
+    }
+}
+// splits the arguments of a color function into its three channels and alpha, and whether it used the old syntax with commas
+// (like rgba(255, 0, 0, 0.5) instead of rgb(255 0 0 / 50%))
+fn color_arguments(arguments: &[ComponentValue]) -> Option<(Vec<&ComponentValue>, Option<&ComponentValue>, bool)> {
+    let values = arguments.iter().filter(|v| v != &&ComponentValue::Token(Token::Whitespace)).collect::<Vec<&ComponentValue>>();
+    if values.contains(&&ComponentValue::Token(Token::Comma)) {
+	let values = values.split(|v| v == &&ComponentValue::Token(Token::Comma)).map(|value| match value {
+	    [value] => Some(*value),
+	    _ => None,
+	}).collect::<Option<Vec<&ComponentValue>>>()?;
+	match values.as_slice() {
+	    [_, _, _] => Some((values, None, true)),
+	    [r, g, b, alpha] => Some((vec![r, g, b], Some(alpha), true)),
+	    _ => None,
+	}
+    } else {
+	match values.as_slice() {
+	    [_, _, _] => Some((values, None, false)),
+	    [r, g, b, ComponentValue::Token(Token::Delim('/')), alpha] => Some((vec![r, g, b], Some(alpha), false)),
+	    _ => None,
+	}
+    }
+}
+// parses a number or percentage in a color, where 100% is full (none is 0, but only in the new syntax)
+fn color_channel(value: &ComponentValue, full: f64, legacy: bool) -> Option<f64> {
+    match value {
+	ComponentValue::Token(Token::Number(n, _)) => Some(*n),
+	ComponentValue::Token(Token::Percentage(n)) => Some(n/100.*full),
+	ComponentValue::Token(Token::Ident(none)) if !legacy && none.eq_ignore_ascii_case("none") => Some(0.),
+	_ => None,
+    }
+}
+// parses a hue in degrees
+fn hue(value: &ComponentValue, legacy: bool) -> Option<f64> {
+    match value {
+	ComponentValue::Token(Token::Dimension(n, _, unit)) => match unit.to_ascii_lowercase().as_str() {
+	    "deg" => Some(*n),
+	    "rad" => Some(n.to_degrees()),
+	    "grad" => Some(n*0.9),
+	    "turn" => Some(n*360.),
+	    _ => None,
+	},
+	ComponentValue::Token(Token::Number(n, _)) => Some(*n),
+	ComponentValue::Token(Token::Ident(none)) if !legacy && none.eq_ignore_ascii_case("none") => Some(0.),
+	_ => None,
+    }
+}
+// turns hue, saturation and lightness into red, green and blue (with everything but hue from 0 to 1)
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> [f64;3] {
+    let hue = hue.rem_euclid(360.);
+    let channel = |n: f64| {
+	let k = (n + hue/30.) % 12.;
+	let a = saturation * lightness.min(1.-lightness);
+	lightness - a * (k-3.).min(9.-k).clamp(-1., 1.)
+    };
+    [channel(0.), channel(8.), channel(4.)]
+}
+// turns hue, whiteness and blackness into red, green and blue
+fn hwb_to_rgb(hue: f64, white: f64, black: f64) -> [f64;3] {
+    // too much white and black is gray
+    if white + black >= 1. {
+	let gray = white / (white + black);
+	return [gray, gray, gray];
+    }
+    hsl_to_rgb(hue, 1., 0.5).map(|channel| channel * (1. - white - black) + white)
+}
+// parses the rrggbb in #rrggbb (or rgb, rgba and rrggbbaa)
+fn parse_hex_color(hex: &str) -> Option<[f64;4]> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+	return None;
+    }
+    let digits = hex.chars().map(|c| c.to_digit(16).unwrap() as f64).collect::<Vec<f64>>();
+    let channels = match digits.len() {
+	// one digit channels are doubled, so f is ff
+	3 | 4 => digits.iter().map(|d| d*17.).collect::<Vec<f64>>(),
+	6 | 8 => digits.chunks(2).map(|pair| pair[0]*16.+pair[1]).collect(),
+	_ => return None,
+    };
+    Some([channels[0]/255., channels[1]/255., channels[2]/255., channels.get(3).map_or(1.0, |a| a/255.)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the style of an element with some declarations, inside of a parent with a style
+    fn style(css: &str, parent: Option<&ComputedStyle>) -> ComputedStyle {
+	let declarations = css::parse_declaration_list(css).into_iter().map(|d| (d.name.clone(), d)).collect();
+	ComputedStyle::compute(&declarations, parent, &Device::default())
+    }
+
+    #[test]
+    fn only_inherited_properties_are_inherited() {
+	let parent = style("color: red; padding-top: 5px; font-size: 20px", None);
+	let child = style("", Some(&parent));
+	assert_eq!(child.color, [1.0, 0.0, 0.0, 1.0]);
+	assert_eq!(child.font_size, 20.);
+	assert_eq!(child.padding_top, Distance::Absolute(0.));
+    }
+
+    #[test]
+    fn em_font_sizes_are_inherited_as_pixels() {
+	let parent = style("font-size: 20px", None);
+	let child = style("font-size: 2em", Some(&parent));
+	assert_eq!(child.font_size, 40.);
+	let grandchild = style("padding-top: 1em", Some(&child));
+	assert_eq!(grandchild.padding_top, Distance::Absolute(40.));
+    }
+
+    #[test]
+    fn inherit_initial_and_unset() {
+	let parent = style("color: red; padding-top: 5px; display: block", None);
+	let child = style("padding-top: inherit; color: initial", Some(&parent));
+	assert_eq!(child.padding_top, Distance::Absolute(5.));
+	assert_eq!(child.color, [0.0, 0.0, 0.0, 1.0]);
+	let child = style("padding-top: unset; color: UNSET; display: unset", Some(&parent));
+	assert_eq!(child.padding_top, Distance::Absolute(0.));
+	assert_eq!(child.color, [1.0, 0.0, 0.0, 1.0]);
+	assert_eq!(child.display, Display::Inline);
+	// keywords work on any property, including through shorthands
+	let child = style("padding: inherit", Some(&parent));
+	assert_eq!(child.padding_top, Distance::Absolute(5.));
+    }
+
+    #[test]
+    fn vars() {
+	let parent = style("--size: 4px; --color: red", None);
+	let child = style("--size: 2px; padding-top: var(--size); padding-left: var( --size ); color: var(--color)", Some(&parent));
+	assert_eq!(child.padding_top, Distance::Absolute(2.));
+	assert_eq!(child.padding_left, Distance::Absolute(2.));
+	assert_eq!(child.color, [1.0, 0.0, 0.0, 1.0]);
+	// custom properties can use each other, and are inherited with their var()s already substituted
+	let child = style("--a: var(--b) 1px; --b: 2px; margin: var(--a)", Some(&parent));
+	assert_eq!((child.margin_top, child.margin_right), (Some(Distance::Absolute(2.)), Some(Distance::Absolute(1.))));
+	assert_eq!(child.custom_properties.get("--a").map(|a| a.trim()), Some("2px 1px"));
+    }
+
+    #[test]
+    fn var_fallbacks() {
+	let child = style("padding-top: var(--missing, 3px); padding-left: var(--missing, var(--also-missing, 5px))", None);
+	assert_eq!(child.padding_top, Distance::Absolute(3.));
+	assert_eq!(child.padding_left, Distance::Absolute(5.));
+	// custom properties set to initial aren't set
+	let child = style("--a: initial; padding-top: var(--a, 1px)", Some(&style("--a: 9px", None)));
+	assert_eq!(child.padding_top, Distance::Absolute(1.));
+    }
+
+    #[test]
+    fn var_cycles_are_invalid() {
+	let parent = style("--a: 7px", None);
+	let child = style("--a: var(--b); --b: var(--a); --c: var(--c); --d: var(--a, 1px); padding-top: var(--a, 2px); padding-left: var(--d, 3px); padding-right: var(--c)", Some(&parent));
+	// the fallback of a property in a cycle is still used, but the custom properties in the cycle aren't set (not even inherited)
+	assert_eq!(child.padding_top, Distance::Absolute(2.));
+	assert_eq!(child.padding_left, Distance::Absolute(1.));
+	assert_eq!(child.padding_right, Distance::Absolute(0.));
+	assert!(child.custom_properties.get("--a").is_none());
+	assert!(child.custom_properties.get("--c").is_none());
+    }
+
+    #[test]
+    fn values_that_dont_work_after_substitution_are_unset() {
+	let parent = style("color: red; padding-top: 5px; font-size: 20px", None);
+	let child = style("--length: 4px; --color: blue; color: var(--length); padding-top: var(--color); font-size: var(--missing)", Some(&parent));
+	// inherited properties get the parent's value, and the rest get their initial value
+	assert_eq!(child.color, [1.0, 0.0, 0.0, 1.0]);
+	assert_eq!(child.padding_top, Distance::Absolute(0.));
+	assert_eq!(child.font_size, 20.);
+    }
+
+    #[test]
+    fn vars_in_shorthands() {
+	let child = style("--x: 1px 2px; padding: var(--x) 3px", None);
+	assert_eq!([child.padding_top, child.padding_right, child.padding_bottom, child.padding_left],
+		   [Distance::Absolute(1.), Distance::Absolute(2.), Distance::Absolute(3.), Distance::Absolute(2.)]);
+	// a longhand after the shorthand still wins
+	let child = style("--x: 1px; padding: var(--x); padding-left: 4px", None);
+	assert_eq!((child.padding_top, child.padding_left), (Distance::Absolute(1.), Distance::Absolute(4.)));
+	// and a shorthand that doesn't work once it's substituted leaves all of its longhands unset
+	let parent = style("margin: 5px", None);
+	let child = style("--x: red; margin: var(--x)", Some(&parent));
+	assert_eq!(child.margin_top, Some(Distance::Absolute(0.)));
+    }
+
+    // the padding-top a value gives, where values that don't work leave it as 1px
+    fn padding(value: &str) -> Distance {
+	style(&format!("padding-top: 1px; padding-top: {value}"), None).padding_top
+    }
+
+    #[test]
+    fn calc() {
+	assert_eq!(padding("calc(100% - 2em)"), Distance::Combo(-24., 1.));
+	assert_eq!(padding("calc(2 * (1px + 2px))"), Distance::Absolute(6.));
+	assert_eq!(padding("calc(10px / 4 + 50% * 2)"), Distance::Combo(2.5, 1.));
+	assert_eq!(padding("CALC(calc(1px) - 3px)"), Distance::Absolute(-2.));
+	// + and - need whitespace around them, numbers and lengths can't be added, and lengths can only be divided by numbers
+	for value in ["calc(1px+2px)", "calc(1px -2px)", "calc(1px + 2)", "calc(1 + 2)", "calc(1px / 1px)", "calc(1px / 0)", "calc(1px 2px)"] {
+	    assert_eq!(padding(value), Distance::Absolute(1.), "{value}");
+	}
+    }
+
+    #[test]
+    fn min_max_and_clamp() {
+	assert_eq!(padding("min(10px, 5%)"), Distance::Clamp((f64::NEG_INFINITY, 0.), (10., 0.), (0., 0.05)));
+	assert_eq!(padding("max(10px, 5%)"), Distance::Clamp((10., 0.), (0., 0.05), (f64::INFINITY, 0.)));
+	assert_eq!(padding("clamp(1px, 50%, 100px)"), Distance::Clamp((1., 0.), (0., 0.5), (100., 0.)));
+	// lengths that always lose are dropped, which leaves one to use
+	assert_eq!(padding("min(3px, 1px, 2px)"), Distance::Absolute(1.));
+	assert_eq!(padding("max(10px, 10px + 5%)"), Distance::Combo(10., 0.05));
+	assert_eq!(padding("min(1px, 2px, 5%)"), Distance::Clamp((f64::NEG_INFINITY, 0.), (1., 0.), (0., 0.05)));
+	// numbers work too
+	assert_eq!(padding("calc(1px * max(2, 3) * clamp(1, 0, 2))"), Distance::Absolute(3.));
+    }
+
+    #[test]
+    fn nested_math_functions() {
+	let distance = padding("calc(min(10px, 5%) + 1px)");
+	assert_eq!((distance.to_pixels(100.), distance.to_pixels(400.)), (6., 11.));
+	let distance = padding("calc(-1 * min(1px, 5%))");
+	assert_eq!((distance.to_pixels(100.), distance.to_pixels(10.)), (-1., -0.5));
+	let distance = padding("max(1px, calc(2 * (5% - 1px)))");
+	assert_eq!((distance.to_pixels(10.), distance.to_pixels(100.)), (1., 8.));
+	let distance = padding("calc((clamp(1px, 10%, 5px) - 1px) / 2)");
+	assert_eq!((distance.to_pixels(0.), distance.to_pixels(30.), distance.to_pixels(100.)), (0., 1., 2.));
+    }
+
+    #[test]
+    fn math_that_cant_be_kept_is_invalid() {
+	// these can't be kept as pixels plus a percent with a min and max, so they are invalid instead of being approximated
+	for value in [
+	    "calc(min(1px, 5%) + max(2px, 3%))",
+	    "min(5px, 5%, 1px + 2%)",
+	    "clamp(min(1px, 2%), 3px, 4px)",
+	    "min(max(1px, 2%), 3px)",
+	    // the min could be bigger than the max, so they can't be swapped
+	    "calc(-1 * clamp(10px, 1px, 5%))",
+	    "calc(0px - clamp(10px, 1px, 5%))",
+	] {
+	    assert_eq!(padding(value), Distance::Absolute(1.), "{value}");
+	}
+    }
+
+    #[test]
+    fn length_units() {
+	let close = |value: &str, pixels: f64| {
+	    let padding = padding(value).to_pixels(0.);
+	    assert!((padding - pixels).abs() < 1e-9, "{value} is {padding} instead of {pixels}");
+	};
+	// absolute units, where an inch is 96 pixels
+	for (value, pixels) in [("3px", 3.), ("1in", 96.), ("2.54cm", 96.), ("25.4mm", 96.), ("101.6q", 96.), ("72pt", 96.), ("6pc", 96.), ("2PX", 2.)] {
+	    close(value, pixels);
+	}
+	// the viewport is 1024 by 768
+	for (value, pixels) in [("10vw", 102.4), ("10vh", 76.8), ("10vmin", 76.8), ("10vmax", 102.4)] {
+	    close(value, pixels);
+	}
+	// 0 doesn't need a unit, but other numbers do, and units have to be known
+	for (value, pixels) in [("0", 0.), ("2", 1.), ("2pixels", 1.)] {
+	    close(value, pixels);
+	}
+    }
+
+    #[test]
+    fn font_relative_units() {
+	let root = style("font-size: 20px", None);
+	assert_eq!(root.root_font_size, 20.);
+	// em is the element's own font size, except in font-size where it's the parent's, and rem is the root's
+	let child = style("font-size: 2em; padding-top: 1em; padding-left: 1rem; padding-right: 2ex; padding-bottom: 2ch", Some(&root));
+	assert_eq!(child.font_size, 40.);
+	assert_eq!((child.padding_top, child.padding_left), (Distance::Absolute(40.), Distance::Absolute(20.)));
+	// ex and ch are guessed as half an em
+	assert_eq!((child.padding_right, child.padding_bottom), (Distance::Absolute(40.), Distance::Absolute(40.)));
+	let grandchild = style("font-size: 1.5rem", Some(&child));
+	assert_eq!((grandchild.font_size, grandchild.root_font_size), (30., 20.));
+    }
+
+    // checks the color a value gives, where values that don't work leave it as blue
+    fn assert_color(value: &str, rgba: [f64;4]) {
+	let color = style(&format!("color: blue; color: {value}"), None).color;
+	assert!(color.iter().zip(rgba).all(|(a, b)| (a - b).abs() < 1e-6), "{value} is {color:?} instead of {rgba:?}");
+    }
+
+    #[test]
+    fn hex_colors() {
+	assert_color("#f00", [1., 0., 0., 1.]);
+	assert_color("#f008", [1., 0., 0., 136./255.]);
+	assert_color("#00FF00", [0., 1., 0., 1.]);
+	assert_color("#ff000080", [1., 0., 0., 128./255.]);
+	for value in ["#12345", "#ff0000f", "#ggg", "#f00f00f00"] {
+	    assert_color(value, [0., 0., 1., 1.]);
+	}
+    }
+
+    #[test]
+    fn rgb_colors() {
+	assert_color("rgb(255, 0, 0)", [1., 0., 0., 1.]);
+	assert_color("RGBA(255, 0, 0, 0.5)", [1., 0., 0., 0.5]);
+	assert_color("rgb(100%, 0%, 0%, 25%)", [1., 0., 0., 0.25]);
+	assert_color("rgba(255 0 0)", [1., 0., 0., 1.]);
+	assert_color("rgb(255 0% none / 50%)", [1., 0., 0., 0.5]);
+	// channels and alpha are kept in range
+	assert_color("rgb(300 -5 0 / 2)", [1., 0., 0., 1.]);
+	// the old syntax can't mix numbers and percentages or use none, and the new one can't have commas
+	for value in ["rgb(255, 0%, 0)", "rgb(255, none, 0)", "rgb(255 0 0, 1)", "rgb(255, 0)", "rgb(255 0 0 0)", "rgb(255 0 0 /)"] {
+	    assert_color(value, [0., 0., 1., 1.]);
+	}
+    }
+
+    #[test]
+    fn hsl_and_hwb_colors() {
+	assert_color("hsl(120, 100%, 50%)", [0., 1., 0., 1.]);
+	assert_color("hsla(0deg 100% 50% / 0.5)", [1., 0., 0., 0.5]);
+	assert_color("hsl(0.5turn 100 50)", [0., 1., 1., 1.]);
+	assert_color("hsl(-240 100% 25%)", [0., 0.5, 0., 1.]);
+	assert_color("hwb(0 0% 0%)", [1., 0., 0., 1.]);
+	assert_color("hwb(0 60% 60%)", [0.5, 0.5, 0.5, 1.]);
+	// saturation and lightness have to be percentages in the old syntax, and hwb only has the new syntax
+	for value in ["hsl(120, 100, 50)", "hwb(0, 0%, 0%)", "hsl(120px 100% 50%)"] {
+	    assert_color(value, [0., 0., 1., 1.]);
+	}
+    }
+
+    #[test]
+    fn named_colors() {
+	assert_color("red", [1., 0., 0., 1.]);
+	assert_color("RebeccaPurple", [0.4, 0.2, 0.6, 1.]);
+	assert_color("CanvasText", [0., 0., 0., 1.]);
+	assert_color("transparent", [0., 0., 0., 0.]);
+	assert_color("notacolor", [0., 0., 1., 1.]);
+	// currentcolor is the parent's color in color, and the element's own color everywhere else
+	let parent = style("color: red", None);
+	let child = style("color: currentcolor; background-color: red", Some(&parent));
+	assert_eq!(child.color, [1., 0., 0., 1.]);
+	let child = style("color: lime; background-color: currentColor", Some(&parent));
+	assert_eq!(child.background_color, [0., 1., 0., 1.]);
+    }
+
+    #[test]
+    fn negative_lengths_are_dropped_where_they_cant_be_used() {
+	assert_eq!(padding("-5px"), Distance::Absolute(1.));
+	assert_eq!(padding("-10%"), Distance::Absolute(1.));
+	let child = style("width: 10px; width: -1px; height: -50%; margin-top: -5px", None);
+	assert_eq!((child.width, child.height, child.margin_top), (Some(Distance::Absolute(10.)), None, Some(Distance::Absolute(-5.))));
+	let value = |value: &str| css::parse_declaration_list(&format!("x: {value}")).remove(0).value;
+	assert!(!is_valid("border-top-width", &value("-1px")));
+	assert!(is_valid("border-top-width", &value("0")));
+	assert!(is_valid("border-top-width", &value("thin")));
+    }
+
+    #[test]
+    fn colors_that_dont_parse_are_dropped() {
+	// the declaration is dropped when it's parsed, so an earlier one still works
+	let child = style("background-color: red; background-color: rgb(1, 2)", None);
+	assert_eq!(child.background_color, [1., 0., 0., 1.]);
+    }
+}
@@ -0,0 +1,662 @@
+use crate::rules;
+
+use std::collections::VecDeque;
+
+// a start or end tag
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    // attributes in the order they appear (repeated attributes are dropped)
+    pub attributes: Vec<(String, String)>,
+    pub self_closing: bool,
+}
+impl Tag {
+    fn new() -> Tag {
+	Tag{name: String::new(), attributes: Vec::new(), self_closing: false}
+    }
+}
+
+// pieces of html that the tokenizer finds
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    // doctype name
+    Doctype(String),
+    StartTag(Tag),
+    EndTag(Tag),
+    Comment(String),
+    Text(String),
+}
+
+// what the tokenizer is in the middle of reading
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum State {
+    Data,
+    // text where the only tag is the end tag (<style>, <script>, etc.)
+    RawText,
+    // like raw text, but with character references (<title>, <textarea>)
+    RcData,
+    // text that goes until the end of the document (<plaintext>)
+    PlainText,
+    TagOpen,
+    EndTagOpen,
+    TagName,
+    BeforeAttributeName,
+    AttributeName,
+    AfterAttributeName,
+    BeforeAttributeValue,
+    // quoted value, with the quote character
+    AttributeValueQuoted(char),
+    AttributeValueUnquoted,
+    AfterAttributeValueQuoted,
+    SelfClosingStartTag,
+    // after <!, not sure yet whether this is a comment, doctype, etc.
+    MarkupDeclarationOpen,
+    Comment,
+    BogusComment,
+    Doctype,
+    // <![CDATA[ ... ]]>, which is only text inside of svg and mathml
+    CDataSection,
+    // possible end tag inside of raw text or rcdata
+    RawTextEndTag,
+    CharacterReference,
+}
+
+// turns html into tokens, a character at a time
+pub struct Tokenizer {
+    state: State,
+    // state to go back to after a character reference or raw text end tag
+    return_state: State,
+    // tokens that are ready
+    tokens: VecDeque<Token>,
+    // text that hasn't been made into a token yet
+    text: String,
+    // tag currently being read
+    tag: Tag,
+    // whether tag is an end tag
+    end_tag: bool,
+    // attribute currently being read
+    attribute_name: String,
+    attribute_value: String,
+    // comment or doctype currently being read
+    comment: String,
+    // characters that might turn out to be something else (like "</sty" or "&am")
+    temp: String,
+    // name of the last start tag, to know where raw text ends
+    last_start_tag: String,
+    // whether the last character was a carriage return (to turn \r\n into \n)
+    last_was_cr: bool,
+    // whether <![CDATA[ starts a cdata section (in svg and mathml) instead of a comment
+    allow_cdata: bool,
+}
+impl Tokenizer {
+    pub fn new() -> Tokenizer {
+	Tokenizer{state: State::Data,
+		  return_state: State::Data,
+		  tokens: VecDeque::new(),
+		  text: String::new(),
+		  tag: Tag::new(),
+		  end_tag: false,
+		  attribute_name: String::new(),
+		  attribute_value: String::new(),
+		  comment: String::new(),
+		  temp: String::new(),
+		  last_start_tag: String::new(),
+		  last_was_cr: false,
+		  allow_cdata: false}
+    }
+    // switches state (the tree builder does this after tags like <style>)
+    pub fn set_state(&mut self, state: State) {
+	self.state = state;
+    }
+    // sets whether cdata sections are allowed (the tree builder does this inside of svg and mathml)
+    pub fn set_allow_cdata(&mut self, allow_cdata: bool) {
+	self.allow_cdata = allow_cdata;
+    }
+    // sets the tag that raw text is ended by
+    pub fn set_last_start_tag(&mut self, name: &str) {
+	self.last_start_tag = name.to_string();
+    }
+    // gets the next token that's ready
+    pub fn next_token(&mut self) -> Option<Token> {
+	self.tokens.pop_front()
+    }
+    // makes a token out of the text read so far, so it can be shown before the rest arrives
+    pub fn flush_text(&mut self) {
+	if !self.text.is_empty() {
+	    let text = std::mem::take(&mut self.text);
+	    self.tokens.push_back(Token::Text(text));
+	}
+    }
+    // reads one more character
+    pub fn feed_char(&mut self, c: char) {
+	// newlines are normalized to \n
+	if c == '\n' && self.last_was_cr {
+	    self.last_was_cr = false;
+	    return;
+	}
+	self.last_was_cr = c == '\r';
+	let c = if c == '\r' { '\n' } else { c };
+	// keep going until the character is used up
+	while self.step(c) {}
+    }
+    // there's nothing left to read
+    pub fn finish(&mut self) {
+	match self.state {
+	    State::TagOpen => self.text.push('<'),
+	    State::EndTagOpen => self.text.push_str("</"),
+	    State::RawTextEndTag => {
+		let temp = std::mem::take(&mut self.temp);
+		self.text.push_str(&temp);
+	    },
+	    State::CharacterReference => self.finish_character_reference(None),
+	    State::MarkupDeclarationOpen => {
+		self.comment = std::mem::take(&mut self.temp);
+		self.emit_comment();
+	    },
+	    State::Comment => {
+		// "<!-- abc --" still ends up as " abc "
+		for end in ["--!", "--", "-"] {
+		    if self.comment.ends_with(end) {
+			self.comment.truncate(self.comment.len()-end.len());
+			break;
+		    }
+		}
+		self.emit_comment();
+	    },
+	    State::BogusComment => self.emit_comment(),
+	    State::Doctype => self.emit_doctype(),
+	    State::CDataSection => {
+		let temp = std::mem::take(&mut self.temp);
+		self.text.push_str(&temp);
+	    },
+	    // unfinished tags are dropped
+	    _ => {},
+	}
+	self.state = State::Data;
+	self.flush_text();
+    }
+    // handles a character, returning whether it needs to be handled again in the new state
+    fn step(&mut self, c: char) -> bool {
+	match self.state {
+	    State::Data => match c {
+		'&' => self.start_character_reference(State::Data),
+		'<' => self.state = State::TagOpen,
+		_ => self.text.push(c),
+	    },
+	    State::RawText => match c {
+		'<' => self.start_raw_text_end_tag(State::RawText),
+		_ => self.text.push(c),
+	    },
+	    State::RcData => match c {
+		'&' => self.start_character_reference(State::RcData),
+		'<' => self.start_raw_text_end_tag(State::RcData),
+		_ => self.text.push(c),
+	    },
+	    State::PlainText => self.text.push(c),
+	    State::TagOpen => match c {
+		'!' => {
+		    self.temp.clear();
+		    self.state = State::MarkupDeclarationOpen;
+		},
+		'/' => self.state = State::EndTagOpen,
+		_ if c.is_ascii_alphabetic() => {
+		    self.start_tag(false);
+		    return true;
+		},
+		'?' => {
+		    self.comment.clear();
+		    self.state = State::BogusComment;
+		    return true;
+		},
+		_ => {
+		    self.text.push('<');
+		    self.state = State::Data;
+		    return true;
+		},
+	    },
+	    State::EndTagOpen => match c {
+		_ if c.is_ascii_alphabetic() => {
+		    self.start_tag(true);
+		    return true;
+		},
+		'>' => self.state = State::Data,
+		_ => {
+		    self.comment.clear();
+		    self.state = State::BogusComment;
+		    return true;
+		},
+	    },
+	    State::TagName => match c {
+		_ if is_whitespace(c) => self.state = State::BeforeAttributeName,
+		'/' => self.state = State::SelfClosingStartTag,
+		'>' => self.emit_tag(),
+		_ => self.tag.name.push(c.to_ascii_lowercase()),
+	    },
+	    State::BeforeAttributeName => match c {
+		_ if is_whitespace(c) => {},
+		'/' | '>' => {
+		    self.state = State::AfterAttributeName;
+		    return true;
+		},
+		'=' => {
+		    self.start_attribute();
+		    self.attribute_name.push(c);
+		    self.state = State::AttributeName;
+		},
+		_ => {
+		    self.start_attribute();
+		    self.state = State::AttributeName;
+		    return true;
+		},
+	    },
+	    State::AttributeName => match c {
+		_ if is_whitespace(c) || c == '/' || c == '>' => {
+		    self.state = State::AfterAttributeName;
+		    return true;
+		},
+		'=' => self.state = State::BeforeAttributeValue,
+		_ => self.attribute_name.push(c.to_ascii_lowercase()),
+	    },
+	    State::AfterAttributeName => match c {
+		_ if is_whitespace(c) => {},
+		'/' => self.state = State::SelfClosingStartTag,
+		'=' => self.state = State::BeforeAttributeValue,
+		'>' => self.emit_tag(),
+		_ => {
+		    self.start_attribute();
+		    self.state = State::AttributeName;
+		    return true;
+		},
+	    },
+	    State::BeforeAttributeValue => match c {
+		_ if is_whitespace(c) => {},
+		'"' | '\'' => self.state = State::AttributeValueQuoted(c),
+		'>' => self.emit_tag(),
+		_ => {
+		    self.state = State::AttributeValueUnquoted;
+		    return true;
+		},
+	    },
+	    State::AttributeValueQuoted(quote) => match c {
+		_ if c == quote => self.state = State::AfterAttributeValueQuoted,
+		'&' => self.start_character_reference(self.state),
+		_ => self.attribute_value.push(c),
+	    },
+	    State::AttributeValueUnquoted => match c {
+		_ if is_whitespace(c) => self.state = State::BeforeAttributeName,
+		'&' => self.start_character_reference(self.state),
+		'>' => self.emit_tag(),
+		_ => self.attribute_value.push(c),
+	    },
+	    State::AfterAttributeValueQuoted => match c {
+		_ if is_whitespace(c) => self.state = State::BeforeAttributeName,
+		'/' => self.state = State::SelfClosingStartTag,
+		'>' => self.emit_tag(),
+		_ => {
+		    self.state = State::BeforeAttributeName;
+		    return true;
+		},
+	    },
+	    State::SelfClosingStartTag => match c {
+		'>' => {
+		    self.tag.self_closing = true;
+		    self.emit_tag();
+		},
+		_ => {
+		    self.state = State::BeforeAttributeName;
+		    return true;
+		},
+	    },
+	    State::MarkupDeclarationOpen => {
+		self.temp.push(c);
+		let lower = self.temp.to_ascii_lowercase();
+		if self.temp == "--" {
+		    self.temp.clear();
+		    self.comment.clear();
+		    self.state = State::Comment;
+		} else if lower == "doctype" {
+		    self.temp.clear();
+		    self.comment.clear();
+		    self.state = State::Doctype;
+		} else if self.temp == "[CDATA[" {
+		    self.temp.clear();
+		    if self.allow_cdata {
+			self.state = State::CDataSection;
+		    } else {
+			// outside of svg and mathml it's a comment
+			self.comment = String::from("[CDATA[");
+			self.state = State::BogusComment;
+		    }
+		} else if !"--".starts_with(&self.temp) && !"doctype".starts_with(&lower) && !"[CDATA[".starts_with(&self.temp) {
+		    // anything else is treated as a comment
+		    self.comment = std::mem::take(&mut self.temp);
+		    self.comment.pop();
+		    self.state = State::BogusComment;
+		    return true;
+		}
+	    },
+	    State::Comment => {
+		if c == '>' && (self.comment.is_empty() || self.comment == "-") {
+		    // <!--> and <!---> are empty comments
+		    self.comment.clear();
+		    self.emit_comment();
+		} else if c == '>' && self.comment.ends_with("--") {
+		    self.comment.truncate(self.comment.len()-2);
+		    self.emit_comment();
+		} else if c == '>' && self.comment.ends_with("--!") {
+		    self.comment.truncate(self.comment.len()-3);
+		    self.emit_comment();
+		} else {
+		    self.comment.push(c);
+		}
+	    },
+	    State::BogusComment => match c {
+		'>' => self.emit_comment(),
+		_ => self.comment.push(c),
+	    },
+	    State::Doctype => match c {
+		'>' => self.emit_doctype(),
+		_ => self.comment.push(c),
+	    },
+	    // temp holds ]s that might be the start of ]]>
+	    State::CDataSection => match c {
+		']' => self.temp.push(c),
+		'>' if self.temp.len() >= 2 => {
+		    let text = self.temp.len()-2;
+		    self.text.push_str(&self.temp[..text]);
+		    self.temp.clear();
+		    self.state = State::Data;
+		},
+		_ => {
+		    let temp = std::mem::take(&mut self.temp);
+		    self.text.push_str(&temp);
+		    self.text.push(c);
+		},
+	    },
+	    State::RawTextEndTag => {
+		if self.temp == "<" {
+		    if c == '/' {
+			self.temp.push(c);
+			return false;
+		    }
+		} else if c.is_ascii_alphabetic() {
+		    self.temp.push(c);
+		    return false;
+		} else if (is_whitespace(c) || c == '/' || c == '>') && self.temp.len() > 2 && self.temp[2..].to_ascii_lowercase() == self.last_start_tag {
+		    // this is the end of the raw text
+		    self.temp.clear();
+		    self.start_tag(true);
+		    self.tag.name = self.last_start_tag.clone();
+		    self.state = State::BeforeAttributeName;
+		    return true;
+		}
+		// wasn't the end tag after all
+		let temp = std::mem::take(&mut self.temp);
+		self.text.push_str(&temp);
+		self.state = self.return_state;
+		return true;
+	    },
+	    State::CharacterReference => {
+		if c.is_ascii_alphanumeric() || (c == '#' && self.temp == "&") {
+		    self.temp.push(c);
+		} else if c == ';' {
+		    self.finish_character_reference(Some(c));
+		} else {
+		    self.finish_character_reference(Some(c));
+		    return true;
+		}
+	    },
+	}
+	false
+    }
+    // starts reading a new tag
+    fn start_tag(&mut self, end_tag: bool) {
+	self.tag = Tag::new();
+	self.end_tag = end_tag;
+	self.attribute_name.clear();
+	self.attribute_value.clear();
+	self.state = State::TagName;
+    }
+    // starts reading a new attribute, finishing the last one
+    fn start_attribute(&mut self) {
+	self.finish_attribute();
+	self.attribute_name.clear();
+	self.attribute_value.clear();
+    }
+    // adds the attribute that was read to the tag
+    fn finish_attribute(&mut self) {
+	if self.attribute_name.is_empty() {
+	    return;
+	}
+	let name = std::mem::take(&mut self.attribute_name);
+	let value = std::mem::take(&mut self.attribute_value);
+	// only the first of repeated attributes counts
+	if !self.tag.attributes.iter().any(|(n, _)| n == &name) {
+	    self.tag.attributes.push((name, value));
+	}
+    }
+    fn emit_tag(&mut self) {
+	self.finish_attribute();
+	self.flush_text();
+	let tag = std::mem::replace(&mut self.tag, Tag::new());
+	if self.end_tag {
+	    self.tokens.push_back(Token::EndTag(tag));
+	} else {
+	    self.last_start_tag = tag.name.clone();
+	    self.tokens.push_back(Token::StartTag(tag));
+	}
+	self.state = State::Data;
+    }
+    fn emit_comment(&mut self) {
+	self.flush_text();
+	let comment = std::mem::take(&mut self.comment);
+	self.tokens.push_back(Token::Comment(comment));
+	self.state = State::Data;
+    }
+    fn emit_doctype(&mut self) {
+	self.flush_text();
+	let name = self.comment.split_whitespace().next().unwrap_or("").to_ascii_lowercase();
+	self.comment.clear();
+	self.tokens.push_back(Token::Doctype(name));
+	self.state = State::Data;
+    }
+    fn start_raw_text_end_tag(&mut self, return_state: State) {
+	self.temp = String::from("<");
+	self.return_state = return_state;
+	self.state = State::RawTextEndTag;
+    }
+    fn start_character_reference(&mut self, return_state: State) {
+	self.temp = String::from("&");
+	self.return_state = return_state;
+	self.state = State::CharacterReference;
+    }
+    // works out what a character reference stands for, given the character after it
+    fn finish_character_reference(&mut self, next: Option<char>) {
+	let reference = std::mem::take(&mut self.temp);
+	let in_attribute = matches!(self.return_state, State::AttributeValueQuoted(_) | State::AttributeValueUnquoted);
+	let mut consumed_semicolon = false;
+	let decoded = if let Some(number) = reference.strip_prefix("&#") {
+	    // numeric references end at the first character that isn't a digit, which is kept as text
+	    let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+		Some(hex) => (hex, 16),
+		None => (number, 10),
+	    };
+	    let length = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+	    if length == 0 {
+		None
+	    } else {
+		// numbers that are too big become the replacement character anyway, so they stop growing
+		let code = digits[..length].chars().fold(0u32, |code, c| code.saturating_mul(radix).saturating_add(c.to_digit(radix).unwrap_or(0)));
+		let rest = &digits[length..];
+		consumed_semicolon = rest.is_empty() && next == Some(';');
+		Some(numeric_character_reference(code).to_string() + rest)
+	    }
+	} else {
+	    let name = &reference[1..];
+	    match find_character_reference(name).filter(|_| next == Some(';')) {
+		Some(value) => {
+		    consumed_semicolon = true;
+		    Some(value.to_string())
+		},
+		// some old named references don't need a semicolon, and can have letters right after them (like "&notit;")
+		None => {
+		    let legacy = rules::LEGACY_CHARACTER_REFERENCES.iter()
+			.filter(|n| name.starts_with(**n))
+			.max_by_key(|n| n.len());
+		    match legacy {
+			// in attributes, "&amp=" or "&ampx" is left alone, since it's probably part of a url
+			Some(legacy) if in_attribute && (legacy.len() < name.len() || next == Some('=')) => None,
+			Some(legacy) => find_character_reference(legacy).map(|value| value.to_string() + &name[legacy.len()..]),
+			None => None,
+		    }
+		},
+	    }
+	};
+	let output = match decoded {
+	    Some(decoded) => decoded,
+	    None => {
+		// not a reference, so it stays as it was
+		consumed_semicolon = false;
+		reference
+	    },
+	};
+	if in_attribute {
+	    self.attribute_value.push_str(&output);
+	} else {
+	    self.text.push_str(&output);
+	}
+	if next == Some(';') && !consumed_semicolon {
+	    if in_attribute {
+		self.attribute_value.push(';');
+	    } else {
+		self.text.push(';');
+	    }
+	}
+	self.state = self.return_state;
+    }
+}
+
+impl Default for Tokenizer {
+    fn default() -> Tokenizer {
+	Tokenizer::new()
+    }
+}
+
+// what a named character reference stands for
+fn find_character_reference(name: &str) -> Option<&'static str> {
+    let index = rules::CHARACTER_REFERENCES.binary_search_by(|(n, _)| (*n).cmp(name)).ok()?;
+    Some(rules::CHARACTER_REFERENCES[index].1)
+}
+
+// what a numeric character reference stands for, following https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+// (null, surrogates and numbers past the last character can't be in text, while noncharacters and other controls are kept as they are)
+fn numeric_character_reference(code: u32) -> char {
+    if let Some((_, replacement)) = rules::NUMERIC_REFERENCE_REPLACEMENTS.iter().find(|(c, _)| *c == code) {
+	return *replacement;
+    }
+    match code {
+	0 => '\u{fffd}',
+	_ => char::from_u32(code).unwrap_or('\u{fffd}'),
+    }
+}
+
+// whitespace as far as html is concerned
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\x0c')
+}
+
+// tokenizes a whole string at once
+pub fn tokenize(html: &str) -> Vec<Token> {
+    let mut tokenizer = Tokenizer::new();
+    for c in html.chars() {
+	tokenizer.feed_char(c);
+    }
+    tokenizer.finish();
+    tokenizer.tokens.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // all the text in some html
+    fn text(html: &str) -> String {
+	tokenize(html).into_iter().filter_map(|token| match token {
+	    Token::Text(text) => Some(text),
+	    _ => None,
+	}).collect()
+    }
+    // the value of the first attribute of the first tag
+    fn attribute(html: &str) -> String {
+	match tokenize(html).into_iter().next() {
+	    Some(Token::StartTag(tag)) => tag.attributes[0].1.clone(),
+	    token => panic!("expected a start tag, got {:?}", token),
+	}
+    }
+
+    #[test]
+    fn named_references() {
+	assert_eq!(text("a &amp; b &lt;&gt;"), "a & b <>");
+	assert_eq!(text("&CounterClockwiseContourIntegral;"), "\u{2233}");
+	// some references are two characters
+	assert_eq!(text("&NotEqualTilde;"), "\u{2242}\u{338}");
+	assert_eq!(text("&nosuchthing;"), "&nosuchthing;");
+    }
+
+    #[test]
+    fn legacy_references_without_semicolons() {
+	assert_eq!(text("&copy 2024"), "\u{a9} 2024");
+	assert_eq!(text("&notit;"), "\u{ac}it;");
+	// only the old references work without a semicolon
+	assert_eq!(text("&hellip"), "&hellip");
+    }
+
+    #[test]
+    fn references_in_attributes() {
+	assert_eq!(attribute("<a href='?a=1&amp;b=2'>"), "?a=1&b=2");
+	// in attributes, references without semicolons that could be part of a url are left alone
+	assert_eq!(attribute("<a href='?x=1&amp=2'>"), "?x=1&amp=2");
+	assert_eq!(attribute("<a href='?x=1&copyright'>"), "?x=1&copyright");
+	assert_eq!(attribute("<a title='&copy 2024'>"), "\u{a9} 2024");
+    }
+
+    #[test]
+    fn numeric_references() {
+	assert_eq!(text("&#65;&#x42;&#X43;"), "ABC");
+	assert_eq!(text("&#65 b"), "A b");
+	assert_eq!(text("&#x;"), "&#x;");
+	// windows-1252 characters in the c1 control range are what was meant
+	assert_eq!(text("&#x80;&#150;"), "\u{20ac}\u{2013}");
+	// codes that aren't characters
+	assert_eq!(text("&#0;&#xd800;&#x110000;&#99999999999999999999;"), "\u{fffd}\u{fffd}\u{fffd}\u{fffd}");
+	// noncharacters are an error, but kept
+	assert_eq!(text("&#xfffe;"), "\u{fffe}");
+    }
+
+    #[test]
+    fn chunks_tokenize_the_same_as_a_whole() {
+	let html = "<p class=\"a &amp; b\">x &notin; y &#x263a;</p><!-- c --><br/>";
+	let mut tokenizer = Tokenizer::new();
+	let mut tokens = Vec::new();
+	for c in html.chars() {
+	    tokenizer.feed_char(c);
+	    while let Some(token) = tokenizer.next_token() {
+		tokens.push(token);
+	    }
+	    tokenizer.flush_text();
+	}
+	tokenizer.finish();
+	while let Some(token) = tokenizer.next_token() {
+	    tokens.push(token);
+	}
+	// text can arrive in more than one piece
+	let merge = |tokens: Vec<Token>| tokens.into_iter().fold(Vec::new(), |mut merged: Vec<Token>, token| {
+	    match (merged.last_mut(), token) {
+		(Some(Token::Text(last)), Token::Text(text)) => last.push_str(&text),
+		(_, token) => merged.push(token),
+	    }
+	    merged
+	});
+	assert_eq!(merge(tokens), merge(tokenize(html)));
+    }
+}
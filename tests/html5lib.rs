@@ -0,0 +1,303 @@
+// runs the html5lib tokenizer and tree construction tests in tests/html5lib
+// cases that are known to fail are listed in tests/html5lib/known-failures.txt, and
+// anything else failing (or a known failure passing) fails the test
+use mehweb::renderer::web::html::{self, Namespace, Node, NodeType};
+use mehweb::renderer::web::tokenizer::{State, Token, Tokenizer};
+
+use serde_json::{json, Value};
+
+use std::collections::HashSet;
+use std::path::Path;
+
+// what happened to each case
+#[derive(Default)]
+struct Report {
+    passed: Vec<String>,
+    failed: Vec<(String, String)>,
+    skipped: Vec<String>,
+}
+impl Report {
+    // prints every case and checks failures against the known ones
+    fn finish(self, name: &str) {
+	let known_failures = known_failures();
+	for case in self.passed.iter() {
+	    println!("PASS {}", case);
+	}
+	for case in self.skipped.iter() {
+	    println!("SKIP {}", case);
+	}
+	let mut unexpected = Vec::new();
+	for (case, details) in self.failed.iter() {
+	    println!("FAIL {}", case);
+	    if !known_failures.contains(case) {
+		unexpected.push(format!("{}\n{}", case, details));
+	    }
+	}
+	// known failures that pass now should be taken off the list
+	for case in self.passed.iter() {
+	    if known_failures.contains(case) {
+		println!("FIXED {}", case);
+		unexpected.push(format!("{}\npasses now, remove it from known-failures.txt", case));
+	    }
+	}
+	println!("{}: {} passed, {} failed, {} skipped", name, self.passed.len(), self.failed.len(), self.skipped.len());
+	if !unexpected.is_empty() {
+	    panic!("{} unexpected results:\n\n{}", unexpected.len(), unexpected.join("\n\n"));
+	}
+    }
+}
+
+fn fixtures_dir() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/html5lib"))
+}
+
+// cases like "tree-construction/tests1.dat:3" (counting from 0), ignoring comments
+fn known_failures() -> HashSet<String> {
+    let text = std::fs::read_to_string(fixtures_dir().join("known-failures.txt")).unwrap_or_default();
+    text.lines()
+	.map(|line| line.split('#').next().unwrap_or("").trim().to_string())
+	.filter(|line| !line.is_empty())
+	.collect()
+}
+
+// fixture files in a directory with an extension, sorted so cases are numbered the same every time
+fn fixtures(directory: &str, extension: &str) -> Vec<(String, String)> {
+    let mut paths = std::fs::read_dir(fixtures_dir().join(directory))
+	.expect("Couldn't read fixtures")
+	.map(|entry| entry.unwrap().path())
+	.filter(|path| path.extension().is_some_and(|e| e == extension))
+	.collect::<Vec<_>>();
+    paths.sort();
+    paths.into_iter().map(|path| {
+	let name = format!("{}/{}", directory, path.file_name().unwrap().to_string_lossy());
+	(name, std::fs::read_to_string(&path).unwrap())
+    }).collect()
+}
+
+#[test]
+fn tokenizer() {
+    let mut report = Report::default();
+    for (file, text) in fixtures("tokenizer", "test") {
+	let tests: Value = serde_json::from_str(&text).expect("Invalid tokenizer test file");
+	for (index, test) in tests["tests"].as_array().unwrap().iter().enumerate() {
+	    let case = format!("{}:{}", file, index);
+	    let description = test["description"].as_str().unwrap_or("");
+	    let double_escaped = test["doubleEscaped"].as_bool().unwrap_or(false);
+	    let mut input = test["input"].as_str().unwrap().to_string();
+	    let mut expected = test["output"].clone();
+	    if double_escaped {
+		input = unescape(&input);
+		expected = unescape_value(&expected);
+	    }
+	    let expected = merge_characters(expected.as_array().unwrap());
+	    let states = match test["initialStates"].as_array() {
+		Some(states) => states.iter().map(|s| s.as_str().unwrap().to_string()).collect(),
+		None => vec![String::from("Data state")],
+	    };
+	    let last_start_tag = test["lastStartTag"].as_str().unwrap_or("");
+	    let mut failure = None;
+	    let mut skipped = false;
+	    for state in states.iter() {
+		let initial_state = match state.as_str() {
+		    "Data state" => State::Data,
+		    "PLAINTEXT state" => State::PlainText,
+		    "RCDATA state" => State::RcData,
+		    // script data is read like raw text
+		    "RAWTEXT state" | "Script data state" => State::RawText,
+		    "CDATA section state" => State::CDataSection,
+		    _ => {
+			skipped = true;
+			continue;
+		    },
+		};
+		let actual = merge_characters(&run_tokenizer(&input, initial_state, last_start_tag));
+		if actual != expected && failure.is_none() {
+		    failure = Some(format!("{} ({})\ninput: {:?}\nexpected: {}\nactual:   {}",
+					   description, state, input, Value::from(expected.clone()), Value::from(actual)));
+		}
+	    }
+	    match failure {
+		Some(details) => report.failed.push((case, details)),
+		None if skipped => report.skipped.push(case),
+		None => report.passed.push(case),
+	    }
+	}
+    }
+    report.finish("tokenizer");
+}
+
+// tokenizes input, giving tokens in the same form as the tests
+fn run_tokenizer(input: &str, state: State, last_start_tag: &str) -> Vec<Value> {
+    let mut tokenizer = Tokenizer::new();
+    tokenizer.set_state(state);
+    tokenizer.set_last_start_tag(last_start_tag);
+    for c in input.chars() {
+	tokenizer.feed_char(c);
+    }
+    tokenizer.finish();
+    let mut tokens = Vec::new();
+    while let Some(token) = tokenizer.next_token() {
+	tokens.push(match token {
+	    // public and system ids aren't kept, and neither is whether the doctype is broken
+	    Token::Doctype(name) => json!(["DOCTYPE", name, null, null, true]),
+	    Token::StartTag(tag) => {
+		let attributes = tag.attributes.into_iter().map(|(name, value)| (name, Value::from(value))).collect::<serde_json::Map<_, _>>();
+		if tag.self_closing {
+		    json!(["StartTag", tag.name, attributes, true])
+		} else {
+		    json!(["StartTag", tag.name, attributes])
+		}
+	    },
+	    Token::EndTag(tag) => json!(["EndTag", tag.name]),
+	    Token::Comment(text) => json!(["Comment", text]),
+	    Token::Text(text) => json!(["Character", text]),
+	});
+    }
+    tokens
+}
+
+// joins character tokens that are next to each other, since the tokenizer can split them up
+fn merge_characters(tokens: &[Value]) -> Vec<Value> {
+    let mut merged: Vec<Value> = Vec::new();
+    for token in tokens {
+	if token[0] == "Character" {
+	    if let Some(last) = merged.last_mut() {
+		if last[0] == "Character" {
+		    let text = last[1].as_str().unwrap().to_string() + token[1].as_str().unwrap();
+		    last[1] = Value::from(text);
+		    continue;
+		}
+	    }
+	}
+	merged.push(token.clone());
+    }
+    merged
+}
+
+// doubly escaped tests have \uXXXX in their strings
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    // surrogate pairs are two escapes, so code units are collected until something else comes
+    let mut units: Vec<u16> = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+	let code = rest.strip_prefix("\\u").and_then(|r| r.get(..4)).and_then(|hex| u16::from_str_radix(hex, 16).ok());
+	match code {
+	    Some(code) => {
+		units.push(code);
+		rest = &rest[6..];
+	    },
+	    None => {
+		unescaped += &String::from_utf16_lossy(&units);
+		units.clear();
+		unescaped.push(c);
+		rest = &rest[c.len_utf8()..];
+	    },
+	}
+    }
+    unescaped + &String::from_utf16_lossy(&units)
+}
+fn unescape_value(value: &Value) -> Value {
+    match value {
+	Value::String(text) => Value::from(unescape(text)),
+	Value::Array(values) => Value::from(values.iter().map(unescape_value).collect::<Vec<_>>()),
+	Value::Object(map) => Value::from(map.iter().map(|(k, v)| (unescape(k), unescape_value(v))).collect::<serde_json::Map<_, _>>()),
+	_ => value.clone(),
+    }
+}
+
+#[test]
+fn tree_construction() {
+    let mut report = Report::default();
+    for (file, text) in fixtures("tree-construction", "dat") {
+	// every case starts with #data
+	let text = format!("\n{}", text);
+	for (index, test) in text.split("\n#data\n").skip(1).enumerate() {
+	    let case = format!("{}:{}", file, index);
+	    let sections = parse_sections(test);
+	    let section = |name: &str| sections.iter().find(|(n, _)| n == name).map(|(_, s)| s.as_str());
+	    // fragments and scripting aren't supported
+	    if section("#document-fragment").is_some() || section("#script-on").is_some() {
+		report.skipped.push(case);
+		continue;
+	    }
+	    let data = section("#data").unwrap_or("");
+	    let expected = section("#document").unwrap_or("");
+	    let actual = dump_tree(&html::parse(data.to_string()));
+	    if actual == expected {
+		report.passed.push(case);
+	    } else {
+		let details = format!("input: {:?}\nexpected:\n{}\nactual:\n{}", data, expected, actual);
+		report.failed.push((case, details));
+	    }
+	}
+    }
+    report.finish("tree construction");
+}
+
+// splits a case into sections like #errors and #document (the data is the start of the case)
+fn parse_sections(test: &str) -> Vec<(String, String)> {
+    let mut sections = vec![(String::from("#data"), Vec::new())];
+    for line in test.split('\n') {
+	let header = ["#errors", "#new-errors", "#document-fragment", "#script-off", "#script-on", "#document"].contains(&line);
+	// text in the document can have lines starting with #
+	if header && sections.last().unwrap().0 != "#document" {
+	    sections.push((line.to_string(), Vec::new()));
+	} else {
+	    sections.last_mut().unwrap().1.push(line);
+	}
+    }
+    sections.into_iter().map(|(name, lines)| {
+	let mut text = lines.join("\n");
+	// the blank line between cases isn't part of the document
+	if name == "#document" && text.ends_with('\n') {
+	    text.pop();
+	}
+	(name, text)
+    }).collect()
+}
+
+// writes a tree the way the tests do
+fn dump_tree(document: &Node) -> String {
+    let mut lines = Vec::new();
+    for child in document.children().borrow().iter() {
+	dump_node(child, 0, &mut lines);
+    }
+    lines.join("\n")
+}
+fn dump_node(node: &Node, depth: usize, lines: &mut Vec<String>) {
+    let indent = format!("| {}", "  ".repeat(depth));
+    match &node.node_type {
+	NodeType::Text(text) => lines.push(format!("{}\"{}\"", indent, text.borrow())),
+	NodeType::Comment(text) => lines.push(format!("{}<!-- {} -->", indent, text.borrow())),
+	NodeType::Doctype(name) => lines.push(format!("{}<!DOCTYPE {}>", indent, name)),
+	NodeType::Container(tag_name, children, params, namespace) => {
+	    let prefix = match namespace {
+		Namespace::Html => "",
+		Namespace::Svg => "svg ",
+		Namespace::MathMl => "math ",
+	    };
+	    lines.push(format!("{}<{}{}>", indent, prefix, tag_name));
+	    // foreign attributes like xlink:href are written as "xlink href"
+	    let mut attributes = params.borrow().iter().map(|(name, value)| {
+		let name = match name.split_once(':') {
+		    Some((prefix, name)) if *namespace != Namespace::Html && ["xlink", "xml", "xmlns"].contains(&prefix) => format!("{} {}", prefix, name),
+		    _ => name.to_string(),
+		};
+		(name, value.to_string())
+	    }).collect::<Vec<_>>();
+	    attributes.sort();
+	    for (name, value) in attributes {
+		lines.push(format!("{}  {}=\"{}\"", indent, name, value));
+	    }
+	    for child in children.borrow().iter() {
+		dump_node(child, depth+1, lines);
+	    }
+	},
+	NodeType::Document(children) => {
+	    for child in children.borrow().iter() {
+		dump_node(child, depth, lines);
+	    }
+	},
+    }
+}
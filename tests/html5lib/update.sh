@@ -0,0 +1,35 @@
+#!/bin/sh
+# copies the tokenizer and tree construction tests from html5lib-tests at a revision,
+# then lists everything that fails as a known failure
+# usage: tests/html5lib/update.sh <commit> (HTML5LIB_TESTS_REPO can point at a local clone)
+set -e
+
+if [ -z "$1" ]; then
+    echo "usage: $0 <html5lib-tests commit>" >&2
+    exit 1
+fi
+revision=$1
+dir=$(cd "$(dirname "$0")" && pwd)
+checkout=$(mktemp -d)
+trap 'rm -rf "$checkout"' EXIT
+
+git clone --quiet "${HTML5LIB_TESTS_REPO:-https://github.com/html5lib/html5lib-tests.git}" "$checkout"
+git -C "$checkout" checkout --quiet "$revision"
+
+# replace the old upstream files, but keep our own (local-*)
+find "$dir/tokenizer" "$dir/tree-construction" -type f ! -name 'local-*' -delete
+cp "$checkout"/tokenizer/*.test "$dir/tokenizer/"
+cp "$checkout"/tree-construction/*.dat "$dir/tree-construction/"
+git -C "$checkout" rev-parse HEAD > "$dir/REVISION"
+
+# run with an empty list so every failing case gets printed as FAIL (git still has the old one)
+: > "$dir/known-failures.txt"
+(cd "$dir/../.." && cargo test --test html5lib -- --nocapture --test-threads=1 || true) | sed -n 's/^FAIL //p' | sort -V > "$dir/failures"
+{
+    echo "# cases that don't pass yet, as file:index (counting from 0)"
+    echo "# take a case off the list once it passes, the test runner fails on ones that do"
+    echo "# generated by update.sh for html5lib-tests $(cat "$dir/REVISION")"
+    echo
+    cat "$dir/failures"
+} > "$dir/known-failures.txt"
+rm "$dir/failures"